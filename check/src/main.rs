@@ -0,0 +1,66 @@
+//! Command-line tool validating beancount files, like `bean-check`
+//!
+//! Usage: `beancount-check <file>...`
+//!
+//! The files and the files they include are read, and the following problems are reported:
+//!
+//! * Syntax errors
+//! * Transactions that do not balance
+//! * References to accounts that are not open, or already closed
+//! * Failed balance assertions
+//!
+//! The problems are printed to the standard error as `file:line: message` (or `message` for the problems of the options), like `bean-check`,
+//! and the exit code is non-zero if any problem is found.
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use beancount_parser::{read_files_v2, Entry, Number, ReadFileErrorV2};
+use rust_decimal::Decimal;
+
+mod validation;
+
+fn main() -> ExitCode {
+    let files: Vec<PathBuf> = env::args_os().skip(1).map(PathBuf::from).collect();
+    if files.is_empty() {
+        eprintln!("Usage: beancount-check <file>...");
+        return ExitCode::from(2);
+    }
+    let mut directives = Vec::new();
+    let mut options = Vec::new();
+    let result = read_files_v2::<Number<Decimal>, _>(files, |entry| match entry {
+        Entry::Directive(directive) => directives.push(directive),
+        Entry::Option(option) => options.push(option),
+        _ => (),
+    });
+    if let Err(err) = result {
+        print_read_error(&err);
+        return ExitCode::FAILURE;
+    }
+    let problems = validation::validate(directives, &options);
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    if problems.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn print_read_error(err: &ReadFileErrorV2) {
+    match err.syntax_error() {
+        Some(syntax_error) => eprintln!(
+            "{}:{}: Invalid beancount syntax",
+            err.path().display(),
+            syntax_error.line_number()
+        ),
+        None => eprintln!("{err}"),
+    }
+    for include in err.include_chain().iter().rev() {
+        eprintln!(
+            "  included from {}:{}",
+            include.from.display(),
+            include.line_number
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+    path::Path,
+    sync::Arc,
+};
+
+use beancount_parser::{
+    check_accounts, check_balances, expand_pads, sort_directives, BeanOption, BookingEngine,
+    BookingError, Date, Directive, DirectiveContent, InterpolationError, Number, Tolerances,
+    Transaction, TransactionBalanceError,
+};
+use rust_decimal::Decimal;
+
+/// Problem found in a directive, or in an option
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) struct Problem {
+    /// Location of the directive, `None` for the problems of the options which have no location
+    location: Option<Location>,
+    message: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Location {
+    date: Date,
+    file: Option<Arc<Path>>,
+    line_number: u32,
+}
+
+impl Problem {
+    fn new(directive: &Directive<Number<Decimal>>, message: String) -> Self {
+        Self {
+            location: Some(Location {
+                date: directive.date,
+                file: directive.file.clone(),
+                line_number: directive.line_number,
+            }),
+            message,
+        }
+    }
+
+    fn option(message: String) -> Self {
+        Self {
+            location: None,
+            message,
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            if let Some(file) = &location.file {
+                write!(f, "{}:", file.display())?;
+            }
+            write!(f, "{}: ", location.line_number)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Returns the problems found in the directives
+///
+/// The directives are processed in the same order as beancount: by date, and on the same date,
+/// the `open` directives first, then the `balance` directives, then the others, and the `close` directives last.
+///
+/// The `pad` directives are expanded into padding transactions before verifying the balance assertions.
+pub(crate) fn validate(
+    mut directives: Vec<Directive<Number<Decimal>>>,
+    options: &[BeanOption],
+) -> Vec<Problem> {
+    sort_directives(&mut directives);
+    let mut ledger = Ledger::new(options);
+    for directive in &directives {
+        ledger.apply(directive);
+    }
+    let (expanded, pad_errors) = expand_pads(&directives, options);
+    // The padding transactions are checked with their `pad` directive, so the same problem may be found twice
+    let mut problems: Vec<Problem> = check_accounts(&expanded, options)
+        .into_iter()
+        .map(|error| Problem::new(error.directive, error.to_string()))
+        .collect();
+    problems.extend(ledger.problems);
+    problems.extend(
+        pad_errors
+            .into_iter()
+            .map(|error| Problem::new(error.directive, error.to_string())),
+    );
+    problems.extend(
+        check_balances(&expanded, options)
+            .into_iter()
+            .map(|error| Problem::new(error.directive, error.to_string())),
+    );
+    // Sorted by location, the problems of the options first, without the duplicates
+    problems
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+struct Ledger {
+    tolerances: Tolerances<Decimal>,
+    booking: BookingEngine<Number<Decimal>>,
+    problems: Vec<Problem>,
+}
+
+impl Ledger {
+    fn new(options: &[BeanOption]) -> Self {
+        let mut problems = Vec::new();
+        let booking = BookingEngine::try_from_options(options).unwrap_or_else(|err| {
+            problems.push(Problem::option(format!("{err} in option booking_method")));
+            BookingEngine::from_options(options)
+        });
+        Self {
+            tolerances: Tolerances::from_options(options),
+            booking,
+            problems,
+        }
+    }
+
+    fn apply(&mut self, directive: &Directive<Number<Decimal>>) {
+        match &directive.content {
+            DirectiveContent::Open(open) => {
+                if let Err(err) = self.booking.open(open) {
+                    self.problems.push(Problem::new(directive, err.to_string()));
+                }
+            }
+            DirectiveContent::Transaction(transaction) => {
+                self.apply_transaction(directive, transaction);
+            }
+            _ => (),
+        }
+    }
+
+    fn apply_transaction(
+        &mut self,
+        directive: &Directive<Number<Decimal>>,
+        transaction: &Transaction<Number<Decimal>>,
+    ) {
+        let booked = match self.booking.book(directive.date, transaction) {
+            Ok(booked) => booked,
+            // The interpolation errors are reported when checking the balance
+            Err(BookingError::Interpolation(_)) => transaction.clone(),
+            Err(err) => {
+                self.problems.push(Problem::new(directive, err.to_string()));
+                transaction.clone()
+            }
+        };
+        let message = match booked.check_balance(&self.tolerances) {
+            Ok(()) => None,
+            // The weights of the postings with a cost but no cost amount depend on the booked lots
+            Err(TransactionBalanceError::Interpolation(InterpolationError::IncompletePosting(
+                _,
+            ))) => return,
+            Err(TransactionBalanceError::Interpolation(InterpolationError::AmbiguousPostings(
+                _,
+            ))) => Some("Transaction has more than one posting without amount".into()),
+            Err(err) => Some(err.to_string()),
+        };
+        if let Some(message) = message {
+            self.problems.push(Problem::new(directive, message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(input: &str) -> Vec<String> {
+        let mut directives = Vec::new();
+        let mut options = Vec::new();
+        for entry in beancount_parser::parse_iter::<Number<Decimal>>(input) {
+            match entry.unwrap() {
+                beancount_parser::Entry::Directive(directive) => directives.push(directive),
+                beancount_parser::Entry::Option(option) => options.push(option),
+                _ => (),
+            }
+        }
+        validate(directives, &options)
+            .into_iter()
+            .map(|problem| problem.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn should_accept_valid_ledger() {
+        let input = r#"
+2024-01-01 open Assets:Cash
+2024-01-01 open Assets:Bank:Checking
+2024-01-01 open Expenses:Food
+2024-01-01 open Equity:Opening
+2024-01-01 pad Assets:Bank:Checking Equity:Opening
+2024-01-02 balance Assets:Bank:Checking 100.00 CHF
+2024-01-02 * "Shop"
+  Assets:Cash -10.004 CHF
+  Expenses:Food 10.00 CHF
+2024-01-03 * "Exchange"
+  Assets:Cash 10 EUR @ 1.10 CHF
+  Assets:Bank:Checking -11.00 CHF
+2024-01-04 * "Auto"
+  Assets:Cash -5 EUR
+  Expenses:Food
+2024-01-05 balance Assets:Cash 5 EUR
+2024-01-05 balance Assets:Cash -10.00 ~ 0.01 CHF
+"#;
+        assert_eq!(problems(input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn should_report_problems() {
+        let input = r#"
+2024-01-01 open Assets:Cash
+2024-01-01 open Expenses:Food
+2024-01-02 * "Unbalanced"
+  Assets:Cash -10.00 CHF
+  Expenses:Food 9.00 CHF
+2024-01-03 * "Ambiguous"
+  Assets:Cash -10.00 CHF
+  Expenses:Food
+  Expenses:Other
+2024-01-04 close Assets:Cash
+2024-01-05 * "Closed"
+  Assets:Cash -1 CHF
+  Expenses:Food 1 CHF
+2024-01-06 balance Expenses:Food 11 CHF
+2024-01-07 pad Expenses:Food Assets:Void
+2024-01-08 open Expenses:Food
+"#;
+        assert_eq!(
+            problems(input),
+            [
+                "4: Transaction does not balance: -1.00 CHF",
+                "7: Account Expenses:Other is not open",
+                "7: Transaction has more than one posting without amount",
+                "11: Account Assets:Cash is not empty",
+                "12: Account Assets:Cash is closed since 2024-01-04",
+                "15: Balance failed for Expenses:Food: expected 11 CHF, but was 10.00 CHF (difference: -1.00 CHF)",
+                "16: Account Assets:Void is not open",
+                "16: Unused pad directive",
+                "17: Account Expenses:Food is already open since 2024-01-01",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_book_reductions() {
+        let input = r#"
+2024-01-01 open Assets:Broker "FIFO"
+2024-01-01 open Assets:Cash "FIFOO"
+2024-01-02 * "Buy"
+  Assets:Broker 10 ACME {100 USD}
+  Assets:Cash
+2024-01-03 * "Buy"
+  Assets:Broker 10 ACME {110 USD}
+  Assets:Cash
+2024-01-04 * "Sell"
+  Assets:Broker -15 ACME {}
+  Assets:Cash
+2024-01-05 * "Sell"
+  Assets:Broker -1 ACME {90 USD}
+  Assets:Cash 90 USD
+2024-01-06 balance Assets:Cash -460 USD
+"#;
+        assert_eq!(
+            problems(input),
+            [
+                "3: Unknown booking method FIFOO",
+                "13: No lot matching the posting of Assets:Broker"
+            ]
+        );
+        let options = "option \"booking_method\" \"fifo\"\n";
+        assert_eq!(
+            problems(&format!("{options}{input}")),
+            [
+                "Unknown booking method fifo in option booking_method",
+                "4: Unknown booking method FIFOO",
+                "14: No lot matching the posting of Assets:Broker"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_overflow() {
+        let input = r#"
+2024-01-01 open Assets:Cash
+2024-01-01 open Assets:Bank
+2024-01-02 * "Exchange"
+  Assets:Cash 79228162514264337593543950335 USD @ 2 EUR
+  Assets:Bank
+"#;
+        assert_eq!(
+            problems(input),
+            ["4: The weight of the posting 0 overflows"]
+        );
+    }
+
+    #[test]
+    fn should_use_tolerance_options() {
+        let input = r#"
+2024-01-01 open Assets:Cash
+2024-01-01 open Assets:Other
+2024-01-02 * "Integer amounts only"
+  Assets:Cash -10 CHF
+  Assets:Other 9 EUR @ 1.11 CHF
+2024-01-03 * "Residual larger than half of the last digit"
+  Assets:Cash -10.00 CHF
+  Assets:Other 10.011 CHF
+"#;
+        assert_eq!(
+            problems(input),
+            [
+                "4: Transaction does not balance: -0.01 CHF",
+                "7: Transaction does not balance: 0.011 CHF"
+            ]
+        );
+        let options = "option \"inferred_tolerance_default\" \"CHF:0.1\"\noption \"inferred_tolerance_multiplier\" \"1.2\"\n";
+        assert_eq!(problems(&format!("{options}{input}")), Vec::<String>::new());
+    }
+}
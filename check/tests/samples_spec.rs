@@ -0,0 +1,58 @@
+#![allow(missing_docs)]
+
+use std::{path::Path, process::Command};
+
+/// Runs the binary on a sample file, and returns its exit code and error output, with the paths relative to the samples directory
+fn check(file: &str) -> (Option<i32>, String) {
+    let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/samples");
+    let output = Command::new(env!("CARGO_BIN_EXE_beancount-check"))
+        .arg(samples.join(file))
+        .output()
+        .unwrap();
+    let prefix = format!("{}/", samples.canonicalize().unwrap().display());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr)
+        .unwrap()
+        .replace(&prefix, "");
+    (output.status.code(), stderr)
+}
+
+#[test]
+fn should_succeed_for_valid_ledger() {
+    assert_eq!(check("valid.beancount"), (Some(0), String::new()));
+}
+
+#[test]
+fn should_report_problems() {
+    let (code, output) = check("problems.beancount");
+    assert_eq!(code, Some(1));
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "problems.beancount:3: Transaction does not balance: -1.00 CHF",
+            "problems.beancount:7: Account Assets:Bank is not open",
+            "problems.beancount:11: Balance failed for Assets:Cash: expected 100.00 CHF, but was 90.00 CHF (difference: -10.00 CHF)",
+        ]
+    );
+}
+
+#[test]
+fn should_report_syntax_error_with_include_chain() {
+    let (code, output) = check("syntax.beancount");
+    assert_eq!(code, Some(1));
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "invalid.beancount:3: Invalid beancount syntax",
+            "  included from syntax.beancount:2",
+        ]
+    );
+}
+
+#[test]
+fn should_fail_without_argument() {
+    let status = Command::new(env!("CARGO_BIN_EXE_beancount-check"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+}
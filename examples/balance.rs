@@ -42,7 +42,9 @@ fn build_report(mut directives: Vec<Directive<Decimal>>) -> Report {
     let mut report = Report::new();
     for directive in directives {
         match directive.content {
-            DirectiveContent::Transaction(trx) => add_trx(&mut report, trx),
+            DirectiveContent::Transaction(trx) => {
+                add_trx(&mut report, trx, directive.line_number);
+            }
             DirectiveContent::Balance(bal) => set_balance(&mut report, bal.account, bal.amount),
             _ => (),
         }
@@ -51,27 +53,17 @@ fn build_report(mut directives: Vec<Directive<Decimal>>) -> Report {
 }
 
 /// Add a transaction to the report
-fn add_trx(report: &mut Report, transaction: Transaction<Decimal>) {
-    // If there is a posting without amount, then it should be consider as a source account for balancing the transaction
-    let source_account = transaction
-        .postings
-        .iter()
-        .find(|p| p.amount.is_none())
-        .map(|p| p.account.clone());
+fn add_trx(report: &mut Report, mut transaction: Transaction<Decimal>, line_number: u32) {
+    // Fill in the amount of the posting without amount, if any, so that the transaction balances
+    if let Err(err) = transaction.interpolate() {
+        eprintln!("Skipping transaction at line {line_number}: {err}");
+        return;
+    }
     transaction
         .postings
         .into_iter()
         .filter_map(|p| Some((p.account, p.amount?)))
-        .for_each(|(account, mut amount)| {
-            // If there is a source account, then we balance the transaction by removing the amount from the source account
-            if let Some(ref source_account) = source_account {
-                add_amount(report, account, amount.clone());
-                amount.value = -amount.value;
-                add_amount(report, source_account.clone(), amount);
-            } else {
-                add_amount(report, account, amount);
-            }
-        });
+        .for_each(|(account, amount)| add_amount(report, account, amount));
 }
 
 fn add_amount(report: &mut Report, account: Account, amount: Amount<Decimal>) {
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use beancount_parser::{DirectiveContent, Entry};
+
+/// Symbols that can be completed
+#[derive(Debug, Default)]
+pub(crate) struct Symbols {
+    pub(crate) accounts: BTreeSet<String>,
+    pub(crate) currencies: BTreeSet<String>,
+    pub(crate) payees: BTreeSet<String>,
+    pub(crate) tags: BTreeSet<String>,
+}
+
+impl Symbols {
+    /// Add the symbols declared or used by the entries
+    pub(crate) fn collect<'a, D: 'a>(&mut self, entries: impl IntoIterator<Item = &'a Entry<D>>) {
+        for entry in entries {
+            let Entry::Directive(directive) = entry else {
+                continue;
+            };
+            match &directive.content {
+                DirectiveContent::Open(open) => {
+                    self.accounts.insert(open.account.as_str().into());
+                }
+                DirectiveContent::Commodity(currency) => {
+                    self.currencies.insert(currency.as_str().into());
+                }
+                DirectiveContent::Transaction(transaction) => {
+                    self.payees.extend(transaction.payee.iter().cloned());
+                    self.tags
+                        .extend(transaction.tags.iter().map(|tag| tag.as_str().into()));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Kind of symbol expected at the cursor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Expected {
+    Account,
+    Currency,
+    Payee,
+    Tag,
+}
+
+/// Returns the kind of symbol expected at the end of the text (which is the text of the line before the cursor),
+/// and the byte offset of the start of the word being typed
+pub(crate) fn expected_at_end(line: &str) -> Option<(Expected, usize)> {
+    let word_start = line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+    let word = &line[word_start..];
+    if word.starts_with('#') {
+        return Some((Expected::Tag, word_start));
+    }
+    if word.starts_with('"') {
+        return is_transaction_header(line).then_some((Expected::Payee, word_start));
+    }
+    let previous: Vec<&str> = line[..word_start].split_whitespace().collect();
+    let is_indented = line.starts_with(char::is_whitespace);
+    let expected = match previous[..] {
+        [] if is_indented => Expected::Account,
+        [_, "open" | "close" | "balance" | "pad" | "note" | "document"] | [_, "pad", _] => {
+            Expected::Account
+        }
+        [_, "commodity"] | [_, "open", _, ..] => Expected::Currency,
+        [.., last] if is_number(last) => Expected::Currency,
+        _ => return None,
+    };
+    Some((expected, word_start))
+}
+
+fn is_transaction_header(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let is_date = words
+        .next()
+        .is_some_and(|word| word.starts_with(|c: char| c.is_ascii_digit()));
+    let is_transaction = words
+        .next()
+        .is_some_and(|word| matches!(word, "*" | "!" | "txn") || word.starts_with('"'));
+    is_date && is_transaction && line.matches('"').count() == 1
+}
+
+fn is_number(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.')
+        && word.ends_with(|c: char| c.is_ascii_digit() || c == '.' || c == ')')
+}
+
+/// Returns the account name around the byte offset of the line, if any
+pub(crate) fn account_at(line: &str, offset: usize) -> Option<&str> {
+    let is_account_char = |c: char| c.is_alphanumeric() || matches!(c, ':' | '-' | '_');
+    let start = line[..offset]
+        .rfind(|c: char| !is_account_char(c))
+        .map_or(0, |index| index + 1);
+    let end = line[offset..]
+        .find(|c: char| !is_account_char(c))
+        .map_or(line.len(), |index| offset + index);
+    let word = &line[start..end];
+    (word.contains(':') && word.starts_with(char::is_uppercase)).then_some(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_expected_symbol() {
+        let cases = [
+            ("  Ass", Some((Expected::Account, 2))),
+            ("2024-01-01 open As", Some((Expected::Account, 16))),
+            ("2024-01-01 pad Assets:A Eq", Some((Expected::Account, 24))),
+            ("2024-01-01 open Assets:A C", Some((Expected::Currency, 25))),
+            ("2024-01-01 commodity ", Some((Expected::Currency, 21))),
+            ("  Assets:A 10.5 ", Some((Expected::Currency, 16))),
+            ("2024-01-01 * \"Sh", Some((Expected::Payee, 13))),
+            ("2024-01-01 * \"Shop\" \"Gro", None),
+            ("2024-01-01 * \"Shop\" #fo", Some((Expected::Tag, 20))),
+            ("2024-01-01 * ", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(expected_at_end(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn should_find_account_at_offset() {
+        let line = "  Assets:Cash  10 CHF";
+        assert_eq!(account_at(line, 2), Some("Assets:Cash"));
+        assert_eq!(account_at(line, 13), Some("Assets:Cash"));
+        assert_eq!(account_at(line, 17), None);
+    }
+}
@@ -0,0 +1,26 @@
+//! A language server for beancount files, built on the `beancount-parser` crate
+//!
+//! It communicates over stdio, and provides:
+//!
+//! * Diagnostics for the syntax errors
+//! * Completion of the accounts (from the `open` directives), currencies (from the `commodity` directives),
+//!   payees and tags (from the transactions)
+//! * Go-to-definition from an account to its `open` directive
+//!
+//! The symbols are collected from the documents opened in the editor.
+
+use std::error::Error;
+
+use lsp_server::Connection;
+
+mod analysis;
+mod position;
+mod server;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+    server::run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use lsp_types::Position;
+
+/// Returns the byte offset of the position, where the character is counted in UTF-16 code units
+///
+/// Positions past the end of a line or of the text are clamped.
+pub(crate) fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+    let line = line_text(text, line_start);
+    let mut utf16_count = 0;
+    for (index, c) in line.char_indices() {
+        if utf16_count >= position.character as usize {
+            return line_start + index;
+        }
+        utf16_count += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Returns the position of the byte offset
+pub(crate) fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    Position::new(
+        to_u32(before.matches('\n').count()),
+        to_u32(before[line_start..].encode_utf16().count()),
+    )
+}
+
+/// Returns the line starting at the offset, without its line ending
+pub(crate) fn line_text(text: &str, line_start: usize) -> &str {
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Returns the offset of the start of the line
+pub(crate) fn line_start(text: &str, line: u32) -> usize {
+    offset(text, Position::new(line, 0))
+}
+
+fn to_u32(value: usize) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_convert_utf16_positions() {
+        let text = "a\nHé😀x\n";
+        for (position, offset) in [
+            ((0, 0), 0),
+            ((1, 0), 2),
+            ((1, 2), 5),
+            ((1, 4), 9),
+            ((2, 0), 11),
+        ] {
+            let position = Position::new(position.0, position.1);
+            assert_eq!(super::offset(text, position), offset);
+            assert_eq!(super::position(text, offset), position);
+        }
+    }
+
+    #[test]
+    fn should_clamp_positions_out_of_bounds() {
+        let text = "ab\ncd";
+        assert_eq!(offset(text, Position::new(0, 10)), 2);
+        assert_eq!(offset(text, Position::new(5, 0)), 5);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, error::Error};
+
+use beancount_parser::{DirectiveContent, Entry, IncrementalParse};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, Request as RequestTrait},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, Diagnostic, DiagnosticSeverity, GotoDefinitionParams,
+    GotoDefinitionResponse, Location, OneOf, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use serde_json::Value;
+
+use crate::{
+    analysis::{account_at, expected_at_end, Expected, Symbols},
+    position::{line_start, line_text, offset, position},
+};
+
+/// Run the server until the client asks for a shutdown
+pub(crate) fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".into(), "#".into(), "\"".into()]),
+            ..CompletionOptions::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    let mut server = Server::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(server.handle_request(request).into())?;
+            }
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.handle_notification(notification) {
+                    connection.sender.send(diagnostics.into())?;
+                }
+            }
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
+
+/// Documents opened in the editor, by URI
+#[derive(Default)]
+struct Server {
+    documents: BTreeMap<Url, IncrementalParse<f64>>,
+}
+
+impl Server {
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        match request.method.as_str() {
+            Completion::METHOD => {
+                respond::<Completion>(id, request, |params| self.complete(&params))
+            }
+            GotoDefinition::METHOD => {
+                respond::<GotoDefinition>(id, request, |params| self.definition(&params))
+            }
+            _ => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", request.method),
+            ),
+        }
+    }
+
+    /// Update the documents, and returns the diagnostics to publish, if any
+    fn handle_notification(&mut self, notification: Notification) -> Option<Notification> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = parse_params::<DidOpenTextDocument>(notification.params)?;
+                let document = params.text_document;
+                self.documents
+                    .insert(document.uri.clone(), IncrementalParse::new(document.text));
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = parse_params::<DidChangeTextDocument>(notification.params)?;
+                let uri = params.text_document.uri;
+                let document = self.documents.get_mut(&uri)?;
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let source = document.source();
+                            let range = offset(source, range.start)..offset(source, range.end);
+                            document.edit(range, &change.text);
+                        }
+                        None => *document = IncrementalParse::new(change.text),
+                    }
+                }
+                uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = parse_params::<DidCloseTextDocument>(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                return Some(publish_diagnostics(uri, Vec::new()));
+            }
+            _ => return None,
+        };
+        let diagnostics = self.documents.get(&uri).map(diagnostics)?;
+        Some(publish_diagnostics(uri, diagnostics))
+    }
+
+    fn complete(&self, params: &CompletionParams) -> Option<CompletionResponse> {
+        let cursor = params.text_document_position.position;
+        let source = self
+            .documents
+            .get(&params.text_document_position.text_document.uri)?
+            .source();
+        let start = line_start(source, cursor.line);
+        let (expected, word_start) = expected_at_end(&source[start..offset(source, cursor)])?;
+        let range = Range::new(position(source, start + word_start), cursor);
+        let mut symbols = Symbols::default();
+        for document in self.documents.values() {
+            symbols.collect(document.entries());
+        }
+        let (names, kind, format): (_, _, fn(&str) -> String) = match expected {
+            Expected::Account => (symbols.accounts, CompletionItemKind::MODULE, str::to_owned),
+            Expected::Currency => (symbols.currencies, CompletionItemKind::UNIT, str::to_owned),
+            Expected::Payee => (symbols.payees, CompletionItemKind::TEXT, |payee| {
+                format!("\"{payee}\"")
+            }),
+            Expected::Tag => (symbols.tags, CompletionItemKind::CONSTANT, |tag| {
+                format!("#{tag}")
+            }),
+        };
+        Some(CompletionResponse::Array(
+            names
+                .into_iter()
+                .map(|name| {
+                    let text = format(&name);
+                    CompletionItem {
+                        label: name,
+                        kind: Some(kind),
+                        filter_text: Some(text.clone()),
+                        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(range, text))),
+                        ..CompletionItem::default()
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn definition(&self, params: &GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let cursor = params.text_document_position_params.position;
+        let source = self
+            .documents
+            .get(&params.text_document_position_params.text_document.uri)?
+            .source();
+        let start = line_start(source, cursor.line);
+        let line = line_text(source, start);
+        let account = account_at(line, (offset(source, cursor) - start).min(line.len()))?;
+        self.documents.iter().find_map(|(uri, document)| {
+            let line_number = document.entries().iter().find_map(|entry| match entry {
+                Entry::Directive(directive) => match &directive.content {
+                    DirectiveContent::Open(open) if open.account.as_str() == account => {
+                        Some(directive.line_number)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })?;
+            let position = Position::new(line_number - 1, 0);
+            Some(GotoDefinitionResponse::Scalar(Location::new(
+                uri.clone(),
+                Range::new(position, position),
+            )))
+        })
+    }
+}
+
+/// Returns the response to the request, using `handle` to compute the result from the parameters
+fn respond<R: RequestTrait>(
+    id: RequestId,
+    request: Request,
+    handle: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(id, handle(params)),
+        Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+    }
+}
+
+fn parse_params<N: NotificationTrait>(params: Value) -> Option<N::Params> {
+    serde_json::from_value(params).ok()
+}
+
+fn diagnostics(document: &IncrementalParse<f64>) -> Vec<Diagnostic> {
+    let Some(error) = document.error() else {
+        return Vec::new();
+    };
+    let source = document.source();
+    let line = error.line_number() - 1;
+    let start = line_start(source, line);
+    let end = position(source, start + line_text(source, start).len());
+    vec![Diagnostic {
+        range: Range::new(Position::new(line, 0), end),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("beancount".into()),
+        message: error.to_string(),
+        ..Diagnostic::default()
+    }]
+}
+
+fn publish_diagnostics(uri: Url, diagnostics: Vec<Diagnostic>) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.into(),
+        PublishDiagnosticsParams::new(uri, diagnostics, None),
+    )
+}
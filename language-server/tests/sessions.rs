@@ -0,0 +1,99 @@
+#![allow(missing_docs)]
+
+//! Replay the recorded JSON-RPC sessions of the `sessions` directory against the server binary
+//!
+//! In a session file, the lines starting with `-->` are sent to the server,
+//! and the lines starting with `<--` are the messages expected from the server, in order.
+//! The other lines are ignored.
+//!
+//! Run with `UPDATE_SESSIONS=1` to record the messages actually sent by the server in the session files.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{ChildStdout, Command, Stdio},
+};
+
+use serde_json::Value;
+
+const SEND: &str = "-->";
+const RECEIVE: &str = "<--";
+
+#[test]
+fn should_replay_recorded_sessions() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sessions");
+    let mut paths: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        replay(&path);
+    }
+}
+
+fn replay(path: &Path) {
+    let session = fs::read_to_string(path).unwrap();
+    let update = std::env::var_os("UPDATE_SESSIONS").is_some();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_beancount-language-server"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = server.stdin.take().unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut recorded = String::new();
+    for (index, line) in session.lines().enumerate() {
+        if let Some(message) = line.strip_prefix(SEND) {
+            let message: Value = serde_json::from_str(message).unwrap();
+            let content = message.to_string();
+            write!(stdin, "Content-Length: {}\r\n\r\n{content}", content.len()).unwrap();
+            stdin.flush().unwrap();
+        } else if let Some(expected) = line.strip_prefix(RECEIVE) {
+            let actual = receive(&mut stdout);
+            if update {
+                recorded.push_str(RECEIVE);
+                recorded.push(' ');
+                recorded.push_str(&actual.to_string());
+                recorded.push('\n');
+                continue;
+            }
+            let expected: Value = serde_json::from_str(expected).unwrap();
+            assert_eq!(
+                actual,
+                expected,
+                "{}:{}: unexpected message from the server",
+                path.display(),
+                index + 1
+            );
+            continue;
+        }
+        recorded.push_str(line);
+        recorded.push('\n');
+    }
+    drop(stdin);
+    assert!(server.wait().unwrap().success(), "{}", path.display());
+    if update {
+        fs::write(path, recorded).unwrap();
+    }
+}
+
+fn receive(stdout: &mut BufReader<ChildStdout>) -> Value {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        stdout.read_line(&mut header).unwrap();
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length: ") {
+            content_length = Some(length.parse().unwrap());
+        }
+    }
+    let mut content = vec![0; content_length.expect("missing content length")];
+    stdout.read_exact(&mut content).unwrap();
+    serde_json::from_slice(&content).unwrap()
+}
@@ -67,7 +67,7 @@ impl FromStr for Account {
     type Err = crate::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let spanned = Span::new(input);
+        let spanned = crate::span(input);
         match all_consuming(parse).parse(spanned).finish() {
             Ok((_, account)) => Ok(account),
             Err(_) => Err(Self::Err::new(input, spanned)),
@@ -109,30 +109,123 @@ impl Open {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
-pub struct BookingMethod(Arc<str>);
+/// Booking method of an account
+///
+/// It defines how the postings reducing the lots held at cost are matched against the lots of the account.
+/// See [`BookingEngine`](crate::BookingEngine).
+///
+/// The methods unknown to beancount are kept as [`BookingMethod::Other`], and are reported by
+/// [`BookingEngine::open`](crate::BookingEngine::open).
+///
+/// # Example
+/// ```
+/// use beancount_parser::{BeancountFile, BookingMethod, DirectiveContent};
+/// let input = r#"
+/// 2022-05-24 open Assets:Broker "FIFO"
+/// 2022-05-24 open Assets:Other "FIFOO"
+/// "#;
+/// let beancount: BeancountFile<f64> = input.parse().unwrap();
+/// let DirectiveContent::Open(open) = &beancount.directives[0].content else { unreachable!() };
+/// assert_eq!(open.booking_method, Some(BookingMethod::Fifo));
+/// let DirectiveContent::Open(open) = &beancount.directives[1].content else { unreachable!() };
+/// assert_eq!(open.booking_method, Some(BookingMethod::Other("FIFOO".into())));
+/// assert_eq!(open.booking_method.as_ref().unwrap().as_str(), "FIFOO");
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub enum BookingMethod {
+    /// `STRICT`: the reducing posting must match a single lot, unless it reduces all the matching lots
+    #[default]
+    Strict,
+    /// `STRICT_WITH_SIZE`: like [`BookingMethod::Strict`], but the oldest lot of the exact size is used if more than one lot match
+    StrictWithSize,
+    /// `FIFO`: the oldest lots are reduced first
+    Fifo,
+    /// `LIFO`: the newest lots are reduced first
+    Lifo,
+    /// `HIFO`: the lots with the highest cost are reduced first
+    Hifo,
+    /// `AVERAGE`: the lots are merged at their average cost
+    Average,
+    /// `NONE`: there is no matching, and the lots of opposite signs may be held at the same time
+    None,
+    /// A booking method unknown to beancount
+    Other(Arc<str>),
+}
+
+impl BookingMethod {
+    /// Returns the name of the booking method, as written in the beancount files
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            BookingMethod::Strict => "STRICT",
+            BookingMethod::StrictWithSize => "STRICT_WITH_SIZE",
+            BookingMethod::Fifo => "FIFO",
+            BookingMethod::Lifo => "LIFO",
+            BookingMethod::Hifo => "HIFO",
+            BookingMethod::Average => "AVERAGE",
+            BookingMethod::None => "NONE",
+            BookingMethod::Other(name) => name,
+        }
+    }
+
+    /// Returns true if the booking method is one of the methods supported by beancount
+    #[must_use]
+    pub fn is_known(&self) -> bool {
+        !matches!(BookingMethod::from(self.as_str()), BookingMethod::Other(_))
+    }
+}
+
+impl PartialEq for BookingMethod {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for BookingMethod {}
+
+impl PartialOrd for BookingMethod {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BookingMethod {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
 
 impl AsRef<str> for BookingMethod {
     fn as_ref(&self) -> &str {
-        &self.0
+        self.as_str()
     }
 }
 
 impl Borrow<str> for BookingMethod {
     fn borrow(&self) -> &str {
-        self.0.borrow()
+        self.as_str()
     }
 }
 
 impl Display for BookingMethod {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&self.0, f)
+        Display::fmt(self.as_str(), f)
     }
 }
 
 impl From<&str> for BookingMethod {
     fn from(value: &str) -> Self {
-        Self(Arc::from(value))
+        match value {
+            "STRICT" => BookingMethod::Strict,
+            "STRICT_WITH_SIZE" => BookingMethod::StrictWithSize,
+            "FIFO" => BookingMethod::Fifo,
+            "LIFO" => BookingMethod::Lifo,
+            "HIFO" => BookingMethod::Hifo,
+            "AVERAGE" => BookingMethod::Average,
+            "NONE" => BookingMethod::None,
+            other => BookingMethod::Other(Arc::from(other)),
+        }
     }
 }
 
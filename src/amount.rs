@@ -11,6 +11,7 @@ use nom::{
     bytes::complete::{take_while, take_while1},
     character::complete::{char, one_of, satisfy, space0, space1},
     combinator::{all_consuming, iterator, map_res, opt, recognize, verify},
+    error::ErrorKind,
     sequence::{delimited, preceded, terminated},
     Finish, Parser,
 };
@@ -95,7 +96,7 @@ impl<'a> TryFrom<&'a str> for Currency {
 impl FromStr for Currency {
     type Err = crate::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let span = Span::new(s);
+        let span = crate::span(s);
         match all_consuming(currency).parse(span).finish() {
             Ok((_, currency)) => Ok(currency),
             Err(_) => Err(crate::Error::new(s, span)),
@@ -115,27 +116,46 @@ pub(crate) fn expression<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
 }
 
 fn sum<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
-    let (input, value) = product(input)?;
-    let mut iter = iterator(input, (delimited(space0, one_of("+-"), space0), product));
-    let value = iter.by_ref().fold(value, |a, (op, b)| match op {
-        '+' => a + b,
-        '-' => a - b,
-        op => unreachable!("unsupported operator: {}", op),
-    });
-    let (input, ()) = iter.finish()?;
-    Ok((input, value))
+    let (rest, value) = product(input)?;
+    let mut iter = iterator(rest, (delimited(space0, one_of("+-"), space0), product));
+    let value = iter
+        .by_ref()
+        .try_fold(value, |a, (op, b)| evaluate(op, a, b));
+    let (rest, ()) = iter.finish()?;
+    match value {
+        Some(value) => Ok((rest, value)),
+        None => Err(invalid_arithmetic(input)),
+    }
 }
 
 fn product<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
-    let (input, value) = atom(input)?;
-    let mut iter = iterator(input, (delimited(space0, one_of("*/"), space0), atom));
-    let value = iter.by_ref().fold(value, |a, (op, b)| match op {
-        '*' => a * b,
-        '/' => a / b,
+    let (rest, value) = atom(input)?;
+    let mut iter = iterator(rest, (delimited(space0, one_of("*/"), space0), atom));
+    let value = iter
+        .by_ref()
+        .try_fold(value, |a, (op, b)| evaluate(op, a, b));
+    let (rest, ()) = iter.finish()?;
+    match value {
+        Some(value) => Ok((rest, value)),
+        None => Err(invalid_arithmetic(input)),
+    }
+}
+
+/// Evaluate a binary operation
+///
+/// Returns `None` if the operation is invalid (for example, `rust_decimal` fails on division by zero or on overflow)
+fn evaluate<D: Decimal>(op: char, a: D, b: D) -> Option<D> {
+    match op {
+        '+' => a.checked_add(b),
+        '-' => a.checked_sub(b),
+        '*' => a.checked_mul(b),
+        '/' => a.checked_div(b),
         op => unreachable!("unsupported operator: {}", op),
-    });
-    let (input, ()) = iter.finish()?;
-    Ok((input, value))
+    }
+}
+
+fn invalid_arithmetic(input: Span<'_>) -> nom::Err<nom::error::Error<Span<'_>>> {
+    nom::Err::Failure(nom::error::Error::new(input, ErrorKind::Verify))
 }
 
 fn atom<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
@@ -143,12 +163,16 @@ fn atom<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
 }
 
 fn group<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
-    delimited(
-        terminated(char('('), space0),
-        expression,
-        preceded(space0, char(')')),
-    )
-    .parse(input)
+    let (rest, _) = terminated(char('('), space0).parse(input)?;
+    let Some(state) = rest.extra.enter_group() else {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            ErrorKind::TooLarge,
+        )));
+    };
+    let (rest, value) =
+        terminated(expression, preceded(space0, char(')'))).parse(rest.map_extra(|_| state))?;
+    Ok((rest.map_extra(|_| input.extra), value))
 }
 
 fn negation<D: Decimal>(input: Span<'_>) -> IResult<'_, D> {
@@ -200,11 +224,21 @@ pub(crate) fn currency(input: Span<'_>) -> IResult<'_, Currency> {
 ///
 /// # Notable implementations
 ///
-/// * `f64`
-/// * `Decimal` of the crate [rust_decimal]
+/// * `f64` and `f32`
+/// * `Decimal` of the crate [rust_decimal] (with the `rust_decimal` feature)
+/// * [`Number`](crate::Number) of any of the above
 ///
 /// [rust_decimal]: https://docs.rs/rust_decimal
 ///
+/// # Arithmetic errors
+///
+/// The expressions are evaluated with the checked operations (like [`Decimal::checked_div`]), and an operation
+/// returning `None` (like a division by zero or an overflow with `rust_decimal`) is reported as an [`Error`](crate::Error)
+/// pointing to the expression.
+///
+/// The default implementations of the checked operations never fail, which is suitable for floating point types.
+/// So another decimal type can implement the trait with `impl Decimal for MyDecimal {}`, and should override
+/// the checked operations if its arithmetic operators may panic.
 pub trait Decimal:
     FromStr
     + Clone
@@ -214,17 +248,93 @@ pub trait Decimal:
     + Div<Output = Self>
     + Neg<Output = Self>
 {
+    /// Returns the sum, or `None` if the operation is invalid
+    #[must_use]
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    /// Returns the difference, or `None` if the operation is invalid
+    #[must_use]
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Some(self - other)
+    }
+
+    /// Returns the product, or `None` if the operation is invalid
+    #[must_use]
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    /// Returns the quotient, or `None` if the operation is invalid
+    #[must_use]
+    fn checked_div(self, other: Self) -> Option<Self> {
+        Some(self / other)
+    }
 }
 
-impl<D> Decimal for D where
-    D: FromStr
-        + Clone
-        + Add<Output = Self>
-        + Sub<Output = Self>
-        + Mul<Output = Self>
-        + Div<Output = Self>
-        + Neg<Output = Self>
-{
+impl Decimal for f64 {}
+
+impl Decimal for f32 {}
+
+#[cfg(feature = "rust_decimal")]
+impl Decimal for rust_decimal::Decimal {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        rust_decimal::Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        rust_decimal::Decimal::checked_sub(self, other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        rust_decimal::Decimal::checked_mul(self, other)
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        rust_decimal::Decimal::checked_div(self, other)
+    }
+}
+
+/// Error returned when the result of an arithmetic operation cannot be computed
+///
+/// It happens when one of the checked operations of the [`Decimal`] type returns `None`,
+/// like on an overflow with `rust_decimal`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct ArithmeticError;
+
+impl Display for ArithmeticError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Arithmetic overflow")
+    }
+}
+
+impl std::error::Error for ArithmeticError {}
+
+/// Returns the absolute value
+pub(crate) fn abs<D: Decimal + PartialOrd + Default>(value: D) -> D {
+    if value < D::default() {
+        -value
+    } else {
+        value
+    }
+}
+
+pub(crate) fn max<D: PartialOrd>(a: D, b: D) -> D {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+pub(crate) fn min<D: PartialOrd>(a: D, b: D) -> D {
+    if b < a {
+        b
+    } else {
+        a
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,591 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    amount::{abs, min},
+    Account, Amount, BeanOption, BookingMethod, Cost, Date, Decimal, InterpolationError, Inventory,
+    Open, Position, Posting, Transaction,
+};
+
+/// Books the transactions against the inventories of the accounts
+///
+/// A posting with a cost reducing the units held by the account (like selling stocks) is matched against the lots
+/// of the account inventory, according to the booking method of the account (see [`Open::booking_method`]).
+/// The booked posting has the complete cost of the lot it reduces, or is split into one posting per lot reduced.
+///
+/// The booking methods are the ones of beancount:
+/// * `STRICT` (the default): the cost of the posting must match a single lot, unless it reduces all the matching lots
+/// * `STRICT_WITH_SIZE`: like `STRICT`, but if multiple lots match, the oldest lot with the exact number of units is reduced
+/// * `FIFO`, `LIFO`: the oldest (or newest) matching lots are reduced first
+/// * `HIFO`: the matching lots with the highest cost are reduced first
+/// * `AVERAGE`: the lots of the currency are merged into a single lot at their average cost before being reduced
+/// * `NONE`: there is no matching, the posting is added to the inventory as is
+///
+/// The transactions must be booked in the order of their date.
+///
+/// See: <https://beancount.github.io/docs/how_inventories_work.html>
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{BeancountFile, BookingEngine, DirectiveContent};
+/// let input = r#"
+/// 2024-01-01 open Assets:Broker "FIFO"
+/// 2024-01-02 * "Buy"
+///   Assets:Broker    10 ACME {100 USD}
+///   Assets:Cash
+/// 2024-01-03 * "Buy"
+///   Assets:Broker    10 ACME {110 USD}
+///   Assets:Cash
+/// 2024-01-04 * "Sell"
+///   Assets:Broker   -15 ACME {}
+///   Assets:Cash
+/// "#;
+/// let beancount: BeancountFile<f64> = input.parse().unwrap();
+/// let mut engine = BookingEngine::new();
+/// let mut booked = Vec::new();
+/// for directive in &beancount.directives {
+///     match &directive.content {
+///         DirectiveContent::Open(open) => engine.open(open).unwrap(),
+///         DirectiveContent::Transaction(trx) => booked.push(engine.book(directive.date, trx).unwrap()),
+///         _ => (),
+///     }
+/// }
+/// let sell = &booked[2];
+/// assert_eq!(sell.postings.len(), 3);
+/// assert_eq!(sell.postings[0].amount.as_ref().unwrap().value, -10.0);
+/// assert_eq!(sell.postings[0].cost.as_ref().unwrap().amount.as_ref().unwrap().value, 100.0);
+/// assert_eq!(sell.postings[1].amount.as_ref().unwrap().value, -5.0);
+/// assert_eq!(sell.postings[1].cost.as_ref().unwrap().amount.as_ref().unwrap().value, 110.0);
+/// assert_eq!(sell.postings[2].amount.as_ref().unwrap().value, 1550.0);
+/// let inventory = engine.inventory("Assets:Broker").unwrap();
+/// assert_eq!(inventory.units("ACME"), Ok(5.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BookingEngine<D> {
+    default_method: BookingMethod,
+    methods: HashMap<Account, BookingMethod>,
+    inventories: HashMap<Account, Inventory<D>>,
+}
+
+impl<D> Default for BookingEngine<D> {
+    fn default() -> Self {
+        Self {
+            default_method: BookingMethod::default(),
+            methods: HashMap::new(),
+            inventories: HashMap::new(),
+        }
+    }
+}
+
+impl<D> BookingEngine<D> {
+    /// Create a booking engine, with empty inventories
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a booking engine using the booking method of the `booking_method` option
+    /// for the accounts opened without booking method
+    ///
+    /// The booking method is `STRICT` if the option is not set, or if its value is not a known booking method.
+    /// Use [`BookingEngine::try_from_options`] to report the unknown booking methods.
+    #[must_use]
+    pub fn from_options(options: &[BeanOption]) -> Self {
+        Self::try_from_options(options).unwrap_or_default()
+    }
+
+    /// Create a booking engine using the booking method of the `booking_method` option
+    /// for the accounts opened without booking method, like [`BookingEngine::from_options`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownBookingMethod`] error if the value of the option is not a known booking method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use beancount_parser::{BeancountFile, BookingEngine, BookingMethod};
+    /// let beancount: BeancountFile<f64> = r#"option "booking_method" "FIFOO""#.parse().unwrap();
+    /// let error = BookingEngine::<f64>::try_from_options(&beancount.options).unwrap_err();
+    /// assert_eq!(error.method, BookingMethod::Other("FIFOO".into()));
+    /// assert_eq!(error.to_string(), "Unknown booking method FIFOO");
+    /// ```
+    pub fn try_from_options(options: &[BeanOption]) -> Result<Self, UnknownBookingMethod> {
+        let mut engine = Self::new();
+        if let Some(method) = options
+            .iter()
+            .rev()
+            .find(|option| option.name == "booking_method")
+            .map(|option| BookingMethod::from(option.value.as_str()))
+        {
+            if !method.is_known() {
+                return Err(UnknownBookingMethod { method });
+            }
+            engine.default_method = method;
+        }
+        Ok(engine)
+    }
+
+    /// Returns the booking method of the accounts opened without booking method
+    #[must_use]
+    pub fn default_method(&self) -> &BookingMethod {
+        &self.default_method
+    }
+
+    /// Record the booking method of the account opened
+    ///
+    /// The default booking method is used if the account has no booking method, or an unknown one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownBookingMethod`] error if the booking method of the account is not a known booking method.
+    /// The account is still opened, with the default booking method.
+    pub fn open(&mut self, open: &Open) -> Result<(), UnknownBookingMethod> {
+        let Some(method) = open.booking_method.clone() else {
+            return Ok(());
+        };
+        if !method.is_known() {
+            return Err(UnknownBookingMethod { method });
+        }
+        self.methods.insert(open.account.clone(), method);
+        Ok(())
+    }
+
+    /// Returns the inventory of the account, if any units were booked to it
+    #[must_use]
+    pub fn inventory(&self, account: &str) -> Option<&Inventory<D>> {
+        self.inventories.get(account)
+    }
+
+    /// Returns the inventories of the accounts to which units were booked
+    pub fn inventories(&self) -> impl Iterator<Item = (&Account, &Inventory<D>)> {
+        self.inventories.iter()
+    }
+
+    fn method(&self, account: &Account) -> BookingMethod {
+        self.methods
+            .get(account)
+            .unwrap_or(&self.default_method)
+            .clone()
+    }
+}
+
+impl<D: Decimal + PartialOrd + Default> BookingEngine<D> {
+    /// Book the transaction, and update the inventories of its accounts
+    ///
+    /// Returns the booked transaction, in which:
+    /// * the reducing postings have the cost of the lot they reduce,
+    ///   and are split into one posting per lot if they reduce more than one lot
+    /// * the augmenting postings with a cost have the date of the transaction as lot date, if they had none
+    /// * the posting without amount, if any, is interpolated (see [`Transaction::interpolate`])
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BookingError`] if a reducing posting matches no lot, or is ambiguous,
+    /// if the booked transaction cannot be interpolated, or if the units of a lot overflow.
+    /// The inventories are left unchanged in case of error.
+    pub fn book(
+        &mut self,
+        date: Date,
+        transaction: &Transaction<D>,
+    ) -> Result<Transaction<D>, BookingError<D>> {
+        let mut saved: Vec<(Account, Option<Inventory<D>>)> = Vec::new();
+        let result = self.try_book(date, transaction, &mut saved);
+        if result.is_err() {
+            for (account, inventory) in saved.into_iter().rev() {
+                match inventory {
+                    Some(inventory) => self.inventories.insert(account, inventory),
+                    None => self.inventories.remove(&account),
+                };
+            }
+        }
+        result
+    }
+
+    fn try_book(
+        &mut self,
+        date: Date,
+        transaction: &Transaction<D>,
+        saved: &mut Vec<(Account, Option<Inventory<D>>)>,
+    ) -> Result<Transaction<D>, BookingError<D>> {
+        let mut booked = transaction.clone();
+        booked.postings = Vec::with_capacity(transaction.postings.len());
+        for posting in &transaction.postings {
+            let postings = match (&posting.amount, &posting.cost) {
+                (Some(units), Some(cost)) => {
+                    self.book_at_cost(date, posting, units, cost, saved)?
+                }
+                _ => vec![posting.clone()],
+            };
+            for posting in &postings {
+                self.apply(posting, saved)?;
+            }
+            booked.postings.extend(postings);
+        }
+        let missing = booked
+            .postings
+            .iter()
+            .position(|posting| posting.amount.is_none());
+        let count = booked.postings.len();
+        booked.interpolate().map_err(BookingError::Interpolation)?;
+        if let Some(index) = missing {
+            let filled = booked.postings.len() + 1 - count;
+            for posting in &booked.postings[index..index + filled] {
+                self.apply(posting, saved)?;
+            }
+        }
+        Ok(booked)
+    }
+
+    /// Returns the booked postings of a posting with a cost
+    fn book_at_cost(
+        &mut self,
+        date: Date,
+        posting: &Posting<D>,
+        units: &Amount<D>,
+        cost: &Cost<D>,
+        saved: &mut Vec<(Account, Option<Inventory<D>>)>,
+    ) -> Result<Vec<Posting<D>>, BookingError<D>> {
+        let zero = D::default();
+        let method = self.method(&posting.account);
+        let is_reduction = method != BookingMethod::None
+            && self
+                .inventories
+                .get(&posting.account)
+                .map_or(false, |inventory| {
+                    inventory.iter().any(|lot| {
+                        lot.cost.is_some()
+                            && lot.units.currency == units.currency
+                            && (lot.units.value < zero) != (units.value < zero)
+                    })
+                });
+        if !is_reduction {
+            let mut posting = posting.clone();
+            if let Some(cost) = &mut posting.cost {
+                if cost.date.is_none() && method != BookingMethod::None {
+                    cost.date = Some(date);
+                }
+            }
+            return Ok(vec![posting]);
+        }
+        if method == BookingMethod::Average {
+            self.average(date, posting, &units.currency, saved)?;
+        }
+        let lots: Vec<Position<D>> = self
+            .inventories
+            .get(&posting.account)
+            .into_iter()
+            .flatten()
+            .filter(|lot| {
+                lot.units.currency == units.currency
+                    && (lot.units.value < zero) != (units.value < zero)
+                    && lot.cost.as_ref().map_or(false, |lot_cost| {
+                        method == BookingMethod::Average || matches(cost, lot_cost)
+                    })
+            })
+            .cloned()
+            .collect();
+        let reductions = reduce(&method, posting, units, lots)?;
+        Ok(reductions
+            .into_iter()
+            .map(|(lot, value)| {
+                let mut posting = posting.clone();
+                posting.amount = Some(Amount {
+                    value,
+                    currency: units.currency.clone(),
+                });
+                posting.cost = lot.cost;
+                posting
+            })
+            .collect())
+    }
+
+    /// Replace the lots of the currency by a single lot at their average cost
+    fn average(
+        &mut self,
+        date: Date,
+        posting: &Posting<D>,
+        currency: &crate::Currency,
+        saved: &mut Vec<(Account, Option<Inventory<D>>)>,
+    ) -> Result<(), BookingError<D>> {
+        let overflow = || BookingError::Overflow(Box::new(posting.clone()));
+        let Some(inventory) = self.inventories.get(&posting.account) else {
+            return Ok(());
+        };
+        let lots: Vec<Position<D>> = inventory
+            .iter()
+            .filter(|lot| &lot.units.currency == currency)
+            .cloned()
+            .collect();
+        let Some(cost_currency) = lots
+            .first()
+            .and_then(|lot| lot.cost.as_ref())
+            .and_then(|cost| cost.amount.as_ref())
+            .map(|amount| amount.currency.clone())
+        else {
+            return Ok(());
+        };
+        let mut total_units = D::default();
+        let mut total_cost = D::default();
+        for lot in &lots {
+            let Some(cost) = lot.cost.as_ref().and_then(|cost| cost.amount.as_ref()) else {
+                return Ok(());
+            };
+            if cost.currency != cost_currency {
+                return Ok(());
+            }
+            total_units = total_units
+                .checked_add(lot.units.value.clone())
+                .ok_or_else(overflow)?;
+            total_cost = lot
+                .units
+                .value
+                .clone()
+                .checked_mul(cost.value.clone())
+                .and_then(|cost| total_cost.checked_add(cost))
+                .ok_or_else(overflow)?;
+        }
+        if lots.len() < 2 || total_units == D::default() {
+            return Ok(());
+        }
+        let value = total_cost
+            .checked_div(total_units.clone())
+            .ok_or_else(overflow)?;
+        self.save(&posting.account, saved);
+        let inventory = self.inventories.entry(posting.account.clone()).or_default();
+        for lot in lots {
+            inventory
+                .add(Position::new(
+                    Amount {
+                        value: -lot.units.value,
+                        currency: lot.units.currency,
+                    },
+                    lot.cost,
+                ))
+                .map_err(|_| overflow())?;
+        }
+        let mut cost = Cost {
+            amount: None,
+            date: Some(date),
+            label: None,
+        };
+        cost.amount = Some(Amount {
+            value,
+            currency: cost_currency,
+        });
+        inventory
+            .add(Position::new(
+                Amount {
+                    value: total_units,
+                    currency: currency.clone(),
+                },
+                Some(cost),
+            ))
+            .map_err(|_| overflow())
+    }
+
+    /// Add the units of the posting to the inventory of its account
+    fn apply(
+        &mut self,
+        posting: &Posting<D>,
+        saved: &mut Vec<(Account, Option<Inventory<D>>)>,
+    ) -> Result<(), BookingError<D>> {
+        let Some(position) = Position::from_posting(posting) else {
+            return Ok(());
+        };
+        self.save(&posting.account, saved);
+        self.inventories
+            .entry(posting.account.clone())
+            .or_default()
+            .add(position)
+            .map_err(|_| BookingError::Overflow(Box::new(posting.clone())))
+    }
+
+    /// Save the inventory of the account before its first change, to restore it in case of error
+    fn save(&self, account: &Account, saved: &mut Vec<(Account, Option<Inventory<D>>)>) {
+        if !saved.iter().any(|(saved, _)| saved == account) {
+            saved.push((account.clone(), self.inventories.get(account).cloned()));
+        }
+    }
+}
+
+/// Returns the lots to reduce, with the units to take from each lot (of the sign of the posting units)
+fn reduce<D: Decimal + PartialOrd + Default>(
+    method: &BookingMethod,
+    posting: &Posting<D>,
+    units: &Amount<D>,
+    mut lots: Vec<Position<D>>,
+) -> Result<Vec<(Position<D>, D)>, BookingError<D>> {
+    let wanted = abs(units.value.clone());
+    let total = lots
+        .iter()
+        .try_fold(D::default(), |total, lot| {
+            total.checked_add(abs(lot.units.value.clone()))
+        })
+        .ok_or_else(|| BookingError::Overflow(Box::new(posting.clone())))?;
+    match method {
+        BookingMethod::Strict | BookingMethod::StrictWithSize | BookingMethod::Other(_) => {
+            if lots.is_empty() {
+                return Err(BookingError::NoMatchingLot(Box::new(posting.clone())));
+            }
+            if lots.len() > 1 && total != wanted {
+                let exact = (*method == BookingMethod::StrictWithSize)
+                    .then(|| {
+                        lots.iter()
+                            .filter(|lot| abs(lot.units.value.clone()) == wanted)
+                            .min_by_key(|lot| lot_date(lot))
+                    })
+                    .flatten()
+                    .cloned();
+                match exact {
+                    Some(lot) => lots = vec![lot],
+                    None => {
+                        return Err(BookingError::AmbiguousMatch(
+                            Box::new(posting.clone()),
+                            lots,
+                        ))
+                    }
+                }
+            }
+        }
+        BookingMethod::Fifo | BookingMethod::Average => lots.sort_by_key(lot_date),
+        BookingMethod::Lifo => {
+            lots.reverse();
+            lots.sort_by_key(|lot| std::cmp::Reverse(lot_date(lot)));
+        }
+        BookingMethod::Hifo => lots.sort_by(|a, b| {
+            let cost = |lot: &Position<D>| {
+                lot.cost
+                    .as_ref()
+                    .and_then(|cost| cost.amount.as_ref())
+                    .map(|amount| amount.value.clone())
+            };
+            cost(b)
+                .partial_cmp(&cost(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        BookingMethod::None => unreachable!("there is no reduction with the booking method NONE"),
+    }
+    if lots.is_empty() {
+        return Err(BookingError::NoMatchingLot(Box::new(posting.clone())));
+    }
+    let mut remaining = wanted;
+    let mut reductions = Vec::new();
+    for lot in &lots {
+        if remaining == D::default() {
+            break;
+        }
+        let taken = min(remaining.clone(), abs(lot.units.value.clone()));
+        remaining = remaining
+            .checked_sub(taken.clone())
+            .ok_or_else(|| BookingError::Overflow(Box::new(posting.clone())))?;
+        let taken = if units.value < D::default() {
+            -taken
+        } else {
+            taken
+        };
+        reductions.push((lot.clone(), taken));
+    }
+    if remaining != D::default() {
+        return Err(BookingError::InsufficientUnits(
+            Box::new(posting.clone()),
+            lots,
+        ));
+    }
+    Ok(reductions)
+}
+
+/// Returns true if the lot cost has the amount, date and label of the cost of the posting (if specified)
+fn matches<D: PartialEq>(spec: &Cost<D>, lot: &Cost<D>) -> bool {
+    spec.amount
+        .as_ref()
+        .map_or(true, |amount| lot.amount.as_ref() == Some(amount))
+        && spec.date.map_or(true, |date| lot.date == Some(date))
+        && spec
+            .label
+            .as_ref()
+            .map_or(true, |label| lot.label.as_ref() == Some(label))
+}
+
+fn lot_date<D>(lot: &Position<D>) -> Option<Date> {
+    lot.cost.as_ref().and_then(|cost| cost.date)
+}
+
+/// Error returned by [`BookingEngine::book`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BookingError<D> {
+    /// No lot matches the cost of the reducing posting
+    NoMatchingLot(Box<Posting<D>>),
+    /// More than one lot matches the cost of the reducing posting
+    ///
+    /// Contains the posting and the matching lots.
+    AmbiguousMatch(Box<Posting<D>>, Vec<Position<D>>),
+    /// The matching lots hold fewer units than the reducing posting
+    ///
+    /// Contains the posting and the matching lots.
+    InsufficientUnits(Box<Posting<D>>, Vec<Position<D>>),
+    /// The booked transaction cannot be interpolated
+    Interpolation(InterpolationError),
+    /// The units or the cost of the lots overflow while booking the posting (see [`Decimal::checked_add`])
+    Overflow(Box<Posting<D>>),
+}
+
+impl<D> Display for BookingError<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookingError::NoMatchingLot(posting) => {
+                write!(f, "No lot matching the posting of {}", posting.account)
+            }
+            BookingError::AmbiguousMatch(posting, lots) => write!(
+                f,
+                "Ambiguous match for the posting of {}: {} lots match",
+                posting.account,
+                lots.len()
+            ),
+            BookingError::InsufficientUnits(posting, _) => write!(
+                f,
+                "Not enough units in the lots matching the posting of {}",
+                posting.account
+            ),
+            BookingError::Interpolation(err) => Display::fmt(err, f),
+            BookingError::Overflow(posting) => write!(
+                f,
+                "Arithmetic overflow booking the posting of {}",
+                posting.account
+            ),
+        }
+    }
+}
+
+impl<D: std::fmt::Debug> std::error::Error for BookingError<D> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BookingError::Interpolation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Booking method that is not one of the booking methods of beancount,
+/// returned by [`BookingEngine::open`] and [`BookingEngine::try_from_options`]
+///
+/// The unknown booking methods are parsed as [`BookingMethod::Other`] rather than rejected by the parser,
+/// so that a file using them can still be read, and the error can name the booking method.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct UnknownBookingMethod {
+    /// The unknown booking method
+    pub method: BookingMethod,
+}
+
+impl Display for UnknownBookingMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown booking method {}", self.method)
+    }
+}
+
+impl std::error::Error for UnknownBookingMethod {}
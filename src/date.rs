@@ -64,7 +64,7 @@ impl FromStr for Date {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let span = Span::new(s);
+        let span = crate::span(s);
         match all_consuming(parse).parse(span).finish() {
             Ok((_, date)) => Ok(date),
             Err(_) => Err(crate::Error::new(s, span)),
@@ -5,16 +5,22 @@
 use std::{
     fmt::{Debug, Display},
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 #[cfg(feature = "miette")]
-use miette::{Diagnostic, SourceSpan};
+use miette::{
+    Diagnostic, LabeledSpan, MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents,
+};
 
-use crate::Span;
+use crate::{Include, Span};
 
 /// Error returned in case of invalid beancount syntax found
 ///
+/// When parsing from a reader with [`parse_reader`](crate::parse_reader),
+/// it may also be caused by a failure to read the input, see [`Error::io_error`].
+///
 /// # Example
 /// ```
 /// # use beancount_parser::BeancountFile;
@@ -33,38 +39,67 @@ pub struct Error {
     #[label]
     span: SourceSpan,
     line_number: u32,
+    io: Option<Arc<io::Error>>,
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Error")
-            .field("line_number", &self.line_number())
-            .finish()
+        let mut debug = f.debug_struct("Error");
+        debug.field("line_number", &self.line_number());
+        if let Some(err) = self.io_error() {
+            debug.field("io", err);
+        }
+        debug.finish()
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid beancount syntax at line: {}", self.line_number)
+        match &self.io {
+            Some(err) => write!(f, "Cannot read input at line {}: {err}", self.line_number),
+            None => write!(f, "Invalid beancount syntax at line: {}", self.line_number),
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io
+            .as_deref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
 
 impl Error {
+    pub(crate) fn new(src: impl Into<String>, span: Span<'_>) -> Self {
+        Self::at(src, span.location_offset(), span.location_line())
+    }
+
+    /// Error at the given byte offset and line number of the source
     #[cfg(not(feature = "miette"))]
-    pub(crate) fn new(_: impl Into<String>, span: Span<'_>) -> Self {
+    pub(crate) fn at(_: impl Into<String>, _: usize, line_number: u32) -> Self {
         Self {
-            line_number: span.location_line(),
+            line_number,
+            io: None,
         }
     }
 
+    /// Error at the given byte offset and line number of the source
     #[cfg(feature = "miette")]
-    pub(crate) fn new(src: impl Into<String>, span: Span<'_>) -> Self {
+    pub(crate) fn at(src: impl Into<String>, offset: usize, line_number: u32) -> Self {
         Self {
             src: src.into(),
-            span: span.location_offset().into(),
-            line_number: span.location_line(),
+            span: offset.into(),
+            line_number,
+            io: None,
+        }
+    }
+
+    /// Error caused by a failure to read the input at the given line number
+    pub(crate) fn from_io(err: io::Error, line_number: u32) -> Self {
+        Self {
+            io: Some(Arc::new(err)),
+            ..Self::at(String::new(), 0, line_number)
         }
     }
 
@@ -73,30 +108,149 @@ impl Error {
     pub fn line_number(&self) -> u32 {
         self.line_number
     }
+
+    /// Returns the IO error, if the input could not be read
+    #[must_use]
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.io.as_deref()
+    }
 }
 
 /// Error returned when reading a beancount file from disk
-#[allow(missing_docs)]
-#[derive(Debug)]
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{fs::InMemoryFileSystem, read_files_from, ReadFileErrorKind, ReadOptions};
+/// let mut fs = InMemoryFileSystem::new();
+/// fs.insert("/main.beancount", "include \"accounts.beancount\"");
+/// fs.insert("/accounts.beancount", "2023-05-27 oops");
+/// let error = read_files_from::<f64, _>(&fs, ["/main.beancount".into()], &ReadOptions::default(), |_| ())
+///     .unwrap_err();
+/// assert_eq!(error.kind(), ReadFileErrorKind::Syntax);
+/// assert_eq!(error.path().to_str(), Some("/accounts.beancount"));
+/// assert_eq!(error.syntax_error().unwrap().line_number(), 1);
+/// assert_eq!(error.include_chain()[0].from.to_str(), Some("/main.beancount"));
+/// ```
 pub struct ReadFileErrorV2 {
     path: PathBuf,
     pub(crate) error: ReadFileErrorContent,
+    pub(crate) include_chain: Vec<Include>,
+    #[cfg(feature = "miette")]
+    source_code: Option<Box<FileSource>>,
+}
+
+/// Kind of [`ReadFileErrorV2`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ReadFileErrorKind {
+    /// The file could not be read, see [`ReadFileErrorV2::io_error`]
+    Io,
+    /// The file contains invalid beancount syntax, see [`ReadFileErrorV2::syntax_error`]
+    Syntax,
+    /// The file is included from outside of the [`ReadOptions::root`](crate::ReadOptions::root) directory
+    OutsideRoot,
+    /// One of the [`Limits`](crate::Limits) is exceeded
+    LimitExceeded,
 }
 
 impl ReadFileErrorV2 {
-    pub(crate) fn from_io(path: PathBuf, err: io::Error) -> Self {
+    fn new(path: PathBuf, error: ReadFileErrorContent) -> Self {
         Self {
+            #[cfg(feature = "miette")]
+            source_code: match &error {
+                ReadFileErrorContent::Syntax(err) => Some(Box::new(FileSource {
+                    name: path.display().to_string(),
+                    src: err.src.clone(),
+                })),
+                _ => None,
+            },
             path,
-            error: ReadFileErrorContent::Io(err),
+            error,
+            include_chain: Vec::new(),
         }
     }
 
+    pub(crate) fn from_io(path: PathBuf, err: io::Error) -> Self {
+        Self::new(path, ReadFileErrorContent::Io(err))
+    }
+
     pub(crate) fn from_syntax(path: PathBuf, err: Error) -> Self {
-        Self {
-            path,
-            error: ReadFileErrorContent::Syntax(err),
+        Self::new(path, ReadFileErrorContent::Syntax(err))
+    }
+
+    pub(crate) fn from_outside_root(path: PathBuf, root: PathBuf) -> Self {
+        Self::new(path, ReadFileErrorContent::OutsideRoot(root))
+    }
+
+    pub(crate) fn from_limit(path: PathBuf, limit: LimitExceeded) -> Self {
+        Self::new(path, ReadFileErrorContent::Limit(limit))
+    }
+
+    /// Record the `include` directive of the `from` file, at `line_number`, that led to the error
+    pub(crate) fn included_from(mut self, from: &Path, line_number: u32) -> Self {
+        self.include_chain.push(Include {
+            from: from.into(),
+            line_number,
+            path: self.path.clone(),
+        });
+        self
+    }
+
+    /// Path of the file that could not be read
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the kind of error
+    #[must_use]
+    pub fn kind(&self) -> ReadFileErrorKind {
+        match &self.error {
+            ReadFileErrorContent::Io(_) => ReadFileErrorKind::Io,
+            ReadFileErrorContent::Syntax(_) => ReadFileErrorKind::Syntax,
+            ReadFileErrorContent::OutsideRoot(_) => ReadFileErrorKind::OutsideRoot,
+            ReadFileErrorContent::Limit(_) => ReadFileErrorKind::LimitExceeded,
         }
     }
+
+    /// Returns the IO error, if the file could not be read
+    #[must_use]
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match &self.error {
+            ReadFileErrorContent::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Returns the syntax error, if the file contains invalid beancount syntax
+    #[must_use]
+    pub fn syntax_error(&self) -> Option<&Error> {
+        match &self.error {
+            ReadFileErrorContent::Syntax(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Returns the `include` directives that led to the file that could not be read
+    ///
+    /// The first directive is in one of the files given to the read function,
+    /// and the last one includes the file that could not be read.
+    /// It is empty if the error is in one of the files given to the read function.
+    #[must_use]
+    pub fn include_chain(&self) -> &[Include] {
+        &self.include_chain
+    }
+}
+
+impl Debug for ReadFileErrorV2 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadFileErrorV2")
+            .field("path", &self.path)
+            .field("error", &self.error)
+            .field("include_chain", &self.include_chain)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Display for ReadFileErrorV2 {
@@ -108,11 +262,91 @@ impl Display for ReadFileErrorV2 {
             ReadFileErrorContent::Syntax(err) => {
                 write!(f, "Invalid syntax in {}: {}", self.path.display(), err)
             }
+            ReadFileErrorContent::OutsideRoot(root) => write!(
+                f,
+                "Cannot include {}: it is outside of {}",
+                self.path.display(),
+                root.display()
+            ),
+            ReadFileErrorContent::Limit(limit) => {
+                write!(f, "Limit exceeded in {}: {}", self.path.display(), limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadFileErrorV2 {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.error {
+            ReadFileErrorContent::Io(err) => Some(err),
+            ReadFileErrorContent::Syntax(err) => Some(err),
+            ReadFileErrorContent::OutsideRoot(_) | ReadFileErrorContent::Limit(_) => None,
         }
     }
 }
 
-impl std::error::Error for ReadFileErrorV2 {}
+#[cfg(feature = "miette")]
+impl Diagnostic for ReadFileErrorV2 {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        if self.include_chain.is_empty() {
+            return None;
+        }
+        Some(Box::new(IncludeChain(&self.include_chain)))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.source_code
+            .as_ref()
+            .map(|source| source.as_ref() as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.syntax_error().and_then(Diagnostic::labels)
+    }
+}
+
+/// Source code of a file, named after its path
+#[cfg(feature = "miette")]
+struct FileSource {
+    name: String,
+    src: String,
+}
+
+#[cfg(feature = "miette")]
+impl SourceCode for FileSource {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let contents = self
+            .src
+            .read_span(span, context_lines_before, context_lines_after)?;
+        Ok(Box::new(MietteSpanContents::new_named(
+            self.name.clone(),
+            contents.data(),
+            *contents.span(),
+            contents.line(),
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+#[cfg(feature = "miette")]
+struct IncludeChain<'a>(&'a [Include]);
+
+#[cfg(feature = "miette")]
+impl Display for IncludeChain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "included from:")?;
+        for include in self.0.iter().rev() {
+            write!(f, "\n  {}:{}", include.from.display(), include.line_number)?;
+        }
+        Ok(())
+    }
+}
 
 /// Content of the error returned when reading a beancount file from disk
 #[allow(missing_docs)]
@@ -120,6 +354,35 @@ impl std::error::Error for ReadFileErrorV2 {}
 pub(crate) enum ReadFileErrorContent {
     Io(std::io::Error),
     Syntax(Error),
+    OutsideRoot(PathBuf),
+    Limit(LimitExceeded),
+}
+
+/// Limit that was exceeded when reading a beancount file from disk
+#[derive(Debug, Clone)]
+pub(crate) enum LimitExceeded {
+    ExpressionDepth { max: usize, line_number: u32 },
+    FileSize(u64),
+    IncludedFiles(usize),
+    LineLength { max: usize, line_number: u32 },
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::ExpressionDepth { max, line_number } => write!(
+                f,
+                "expression at line {line_number} is nested more than {max} levels deep"
+            ),
+            LimitExceeded::FileSize(max) => write!(f, "file is larger than {max} bytes"),
+            LimitExceeded::IncludedFiles(max) => {
+                write!(f, "more than {max} files are included")
+            }
+            LimitExceeded::LineLength { max, line_number } => {
+                write!(f, "line {line_number} is longer than {max} bytes")
+            }
+        }
+    }
 }
 
 /// Content of the error returned when reading a beancount file from disk
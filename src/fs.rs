@@ -0,0 +1,219 @@
+//! Abstraction over the file system from which beancount files are read
+//!
+//! See [`read_files_from`](crate::read_files_from)
+//!
+//! # Example
+//!
+//! ```
+//! use beancount_parser::{fs::InMemoryFileSystem, read_files_from, BeancountFile, ReadOptions};
+//! let mut fs = InMemoryFileSystem::new();
+//! fs.insert("/ledger/main.beancount", "include \"accounts.beancount\"");
+//! fs.insert("/ledger/accounts.beancount", "2023-05-27 open Assets:Cash");
+//! let mut file = BeancountFile::<f64>::default();
+//! read_files_from(&fs, ["/ledger/main.beancount".into()], &ReadOptions::default(), |entry| {
+//!     file.extend(Some(entry));
+//! })
+//! .unwrap();
+//! assert_eq!(file.directives.len(), 1);
+//! assert_eq!(file.includes[0].to_str(), Some("/ledger/accounts.beancount"));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+/// File system from which beancount files are read
+pub trait FileSystem {
+    /// Open the file for reading
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist or cannot be read
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Returns the canonical form of the path
+    ///
+    /// Two paths designating the same file must have the same canonical form.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path does not exist
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Returns the paths of the files matching the glob pattern
+    ///
+    /// The default implementation returns an error of kind [`io::ErrorKind::Unsupported`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid or if the file system does not support glob patterns
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("glob patterns are not supported: {pattern}"),
+        ))
+    }
+}
+
+/// The file system of the operating system, using [`std::fs`]
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        glob::glob(pattern)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+            .map(|result| result.map_err(io::Error::from))
+            .collect()
+    }
+}
+
+/// A file system whose files are held in memory
+///
+/// Paths are normalized lexically (`.` and `..` components are resolved), and there are no symbolic links.
+///
+/// See the [module documentation](self) for an example
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryFileSystem {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl InMemoryFileSystem {
+    /// Create an empty file system
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a file, replacing the previous content if the file already exists
+    pub fn insert(&mut self, path: impl AsRef<Path>, content: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), content.into());
+    }
+}
+
+impl FileSystem for InMemoryFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.files.get(&normalize(path)) {
+            Some(content) => Ok(Box::new(content.as_bytes())),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = normalize(path);
+        if self.files.keys().any(|file| file.starts_with(&path)) {
+            Ok(path)
+        } else {
+            Err(not_found(&path))
+        }
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        // The pattern is normalized like the paths of the files, so that `.` and `..` components can match
+        let pattern = normalize(Path::new(pattern));
+        let pattern = glob::Pattern::new(&pattern.to_string_lossy())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(self
+            .files
+            .keys()
+            .filter(|path| pattern.matches_path(path))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Resolve the `.` and `..` components of the path lexically
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("file not found: {}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("/a/b.beancount", "/a/b.beancount")]
+    #[case("/a/./b.beancount", "/a/b.beancount")]
+    #[case("/a/c/../b.beancount", "/a/b.beancount")]
+    #[case("a/b.beancount", "a/b.beancount")]
+    #[case("./a/b.beancount", "a/b.beancount")]
+    fn in_memory_canonicalize_should_normalize_path(#[case] input: &str, #[case] expected: &str) {
+        let mut fs = InMemoryFileSystem::new();
+        fs.insert("/a/b.beancount", "");
+        fs.insert("a/b.beancount", "");
+        assert_eq!(
+            fs.canonicalize(Path::new(input)).unwrap(),
+            Path::new(expected)
+        );
+    }
+
+    #[rstest]
+    fn in_memory_canonicalize_should_accept_directory() {
+        let mut fs = InMemoryFileSystem::new();
+        fs.insert("/a/b.beancount", "");
+        assert_eq!(fs.canonicalize(Path::new("/a/")).unwrap(), Path::new("/a"));
+    }
+
+    #[rstest]
+    fn in_memory_canonicalize_should_fail_for_missing_file() {
+        let fs = InMemoryFileSystem::new();
+        let error = fs.canonicalize(Path::new("/a.beancount")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[rstest]
+    fn in_memory_glob_should_return_sorted_matches() {
+        let mut fs = InMemoryFileSystem::new();
+        fs.insert("/a/2.beancount", "");
+        fs.insert("/a/1.beancount", "");
+        fs.insert("/b/3.beancount", "");
+        let paths = fs.glob("/a/*.beancount").unwrap();
+        assert_eq!(
+            paths,
+            [Path::new("/a/1.beancount"), Path::new("/a/2.beancount")]
+        );
+    }
+
+    #[rstest]
+    #[case("/a/./*.beancount")]
+    #[case("/b/../a/*.beancount")]
+    #[case("/a/c/./../*.beancount")]
+    fn in_memory_glob_should_normalize_pattern(#[case] pattern: &str) {
+        let mut fs = InMemoryFileSystem::new();
+        fs.insert("/a/1.beancount", "");
+        fs.insert("/b/2.beancount", "");
+        assert_eq!(fs.glob(pattern).unwrap(), [Path::new("/a/1.beancount")]);
+    }
+}
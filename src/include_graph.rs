@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+/// Graph of the `include` directives found when reading files
+///
+/// It is returned by the functions reading files with options, like [`read_files_with_options`](crate::read_files_with_options).
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{fs::InMemoryFileSystem, read_files_from, IncludeDiagnostic, ReadOptions};
+/// let mut fs = InMemoryFileSystem::new();
+/// fs.insert("/main.beancount", "include \"accounts.beancount\"");
+/// fs.insert("/accounts.beancount", "include \"main.beancount\"");
+/// let graph = read_files_from::<f64, _>(&fs, ["/main.beancount".into()], &ReadOptions::default(), |_| ()).unwrap();
+/// assert_eq!(graph.includes().len(), 2);
+/// assert_eq!(graph.includes()[0].line_number, 1);
+/// let diagnostics = graph.diagnostics();
+/// assert!(matches!(diagnostics[0], IncludeDiagnostic::Cycle(_)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IncludeGraph {
+    includes: Vec<Include>,
+}
+
+/// An `include` directive, with the canonical paths of the including and included files
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Include {
+    /// File containing the `include` directive
+    pub from: PathBuf,
+    /// Line number of the `include` directive
+    pub line_number: u32,
+    /// Included file
+    ///
+    /// If the directive is a glob pattern, there is one [`Include`] for each matched file.
+    pub path: PathBuf,
+}
+
+impl Display for Include {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: include {}",
+            self.from.display(),
+            self.line_number,
+            self.path.display()
+        )
+    }
+}
+
+/// Issue found in the [`IncludeGraph`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum IncludeDiagnostic {
+    /// The `include` directives form a cycle
+    ///
+    /// The directives are listed in order, so that the last one includes the file containing the first one.
+    Cycle(Vec<Include>),
+    /// The file is included by more than one `include` directive
+    ///
+    /// All the directives including the file are listed, in the order they were found.
+    Duplicate(Vec<Include>),
+}
+
+impl Display for IncludeDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeDiagnostic::Cycle(includes) => {
+                write!(f, "Include cycle:")?;
+                for include in includes {
+                    write!(f, "\n  {include}")?;
+                }
+            }
+            IncludeDiagnostic::Duplicate(includes) => {
+                if let Some(include) = includes.first() {
+                    write!(f, "{} is included multiple times:", include.path.display())?;
+                }
+                for include in includes {
+                    write!(f, "\n  {include}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IncludeGraph {
+    pub(crate) fn push(&mut self, include: Include) {
+        self.includes.push(include);
+    }
+
+    /// Returns all the `include` directives, in the order they were found
+    #[must_use]
+    pub fn includes(&self) -> &[Include] {
+        &self.includes
+    }
+
+    /// Returns the `include` directives found in the given file
+    pub fn included_by<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Include> + 'a {
+        self.includes
+            .iter()
+            .filter(move |include| include.from == path)
+    }
+
+    /// Returns the `include` directives including the given file
+    pub fn including<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Include> + 'a {
+        self.includes
+            .iter()
+            .filter(move |include| include.path == path)
+    }
+
+    /// Returns the include cycles and the files included multiple times
+    ///
+    /// The cycles are reported first, then the duplicates.
+    #[must_use]
+    pub fn diagnostics(&self) -> Vec<IncludeDiagnostic> {
+        let mut diagnostics: Vec<_> = self
+            .cycles()
+            .into_iter()
+            .map(IncludeDiagnostic::Cycle)
+            .collect();
+        // The includes of each file, in the order the files were first included
+        let mut indices: HashMap<&Path, usize> = HashMap::new();
+        let mut by_path: Vec<Vec<Include>> = Vec::new();
+        for include in &self.includes {
+            let index = *indices.entry(&include.path).or_insert_with(|| {
+                by_path.push(Vec::new());
+                by_path.len() - 1
+            });
+            by_path[index].push(include.clone());
+        }
+        diagnostics.extend(
+            by_path
+                .into_iter()
+                .filter(|includes| includes.len() > 1)
+                .map(IncludeDiagnostic::Duplicate),
+        );
+        diagnostics
+    }
+
+    /// Find the cycles with a depth-first search, reporting a cycle for each back edge
+    fn cycles(&self) -> Vec<Vec<Include>> {
+        let mut edges: HashMap<&Path, Vec<&Include>> = HashMap::new();
+        for include in &self.includes {
+            edges.entry(&include.from).or_default().push(include);
+        }
+        let mut visited: HashMap<&Path, bool> = HashMap::new();
+        let mut cycles = Vec::new();
+        for start in self.includes.iter().map(|include| include.from.as_path()) {
+            if visited.contains_key(start) {
+                continue;
+            }
+            // Stack of the include directives being explored, with the index of the next edge to explore
+            let mut stack: Vec<(&Path, Option<&Include>, usize)> = vec![(start, None, 0)];
+            visited.insert(start, true);
+            while let Some((node, _, index)) = stack.last_mut() {
+                let Some(include) = edges.get(node).and_then(|edges| edges.get(*index)) else {
+                    visited.insert(node, false);
+                    stack.pop();
+                    continue;
+                };
+                *index += 1;
+                match visited.get(include.path.as_path()) {
+                    Some(true) => {
+                        let position = stack
+                            .iter()
+                            .position(|(node, _, _)| *node == include.path)
+                            .unwrap_or_default();
+                        let mut cycle: Vec<Include> = stack[position + 1..]
+                            .iter()
+                            .filter_map(|(_, include, _)| include.cloned())
+                            .collect();
+                        cycle.push((*include).clone());
+                        cycles.push(cycle);
+                    }
+                    Some(false) => (),
+                    None => {
+                        visited.insert(&include.path, true);
+                        stack.push((&include.path, Some(include), 0));
+                    }
+                }
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn graph(includes: &[(&str, &str)]) -> IncludeGraph {
+        let mut graph = IncludeGraph::default();
+        for (line, (from, path)) in includes.iter().enumerate() {
+            graph.push(Include {
+                from: from.into(),
+                line_number: u32::try_from(line).unwrap() + 1,
+                path: path.into(),
+            });
+        }
+        graph
+    }
+
+    fn paths(includes: &[Include]) -> Vec<(&str, &str)> {
+        includes
+            .iter()
+            .map(|i| (i.from.to_str().unwrap(), i.path.to_str().unwrap()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::tree(&[("a", "b"), ("a", "c"), ("b", "d")])]
+    #[case::chain(&[("a", "b"), ("b", "c"), ("c", "d")])]
+    fn should_not_report_anything(#[case] includes: &[(&str, &str)]) {
+        assert_eq!(graph(includes).diagnostics(), []);
+    }
+
+    #[rstest]
+    #[case::self_include(&[("a", "a")])]
+    #[case::indirect(&[("a", "b"), ("b", "c"), ("c", "a")])]
+    fn should_report_cycle(#[case] includes: &[(&str, &str)]) {
+        let graph = graph(includes);
+        let [IncludeDiagnostic::Cycle(cycle)] = &graph.diagnostics()[..] else {
+            panic!("unexpected diagnostics: {:?}", graph.diagnostics());
+        };
+        assert_eq!(paths(cycle), includes);
+    }
+
+    #[rstest]
+    fn should_report_cycle_and_duplicate_when_entering_cycle_from_outside() {
+        let graph = graph(&[("a", "b"), ("b", "c"), ("c", "b")]);
+        let [IncludeDiagnostic::Cycle(cycle), IncludeDiagnostic::Duplicate(duplicate)] =
+            &graph.diagnostics()[..]
+        else {
+            panic!("unexpected diagnostics: {:?}", graph.diagnostics());
+        };
+        assert_eq!(paths(cycle), [("b", "c"), ("c", "b")]);
+        assert_eq!(paths(duplicate), [("a", "b"), ("c", "b")]);
+    }
+
+    #[rstest]
+    fn should_report_duplicate_include() {
+        let graph = graph(&[("a", "b"), ("a", "c"), ("c", "b")]);
+        let [IncludeDiagnostic::Duplicate(includes)] = &graph.diagnostics()[..] else {
+            panic!("unexpected diagnostics: {:?}", graph.diagnostics());
+        };
+        assert_eq!(paths(includes), [("a", "b"), ("c", "b")]);
+    }
+
+    #[rstest]
+    fn should_report_duplicates_in_order_of_first_include() {
+        let graph = graph(&[("a", "c"), ("a", "b"), ("b", "c"), ("c", "b")]);
+        let diagnostics = graph.diagnostics();
+        let duplicates: Vec<_> = diagnostics
+            .iter()
+            .filter_map(|diagnostic| match diagnostic {
+                IncludeDiagnostic::Duplicate(includes) => Some(paths(includes)),
+                IncludeDiagnostic::Cycle(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            [vec![("a", "c"), ("b", "c")], vec![("a", "b"), ("c", "b")]]
+        );
+    }
+}
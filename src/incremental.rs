@@ -0,0 +1,291 @@
+use std::ops::Range;
+
+use crate::{iterator::TagStack, reader::is_anchor, span, Decimal, Entry, Error, RawEntry, Tag};
+
+/// Entries parsed from a source, that can be updated after each edit of the source without parsing it again entirely
+///
+/// Only the directives affected by an edit are parsed again, which makes it suitable for editors
+/// that need the entries after each keystroke.
+/// The entries and the error are always the same as with [`parse_iter`](crate::parse_iter) on the edited source.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{IncrementalParse, Entry};
+/// let mut parse = IncrementalParse::<f64>::new("2023-05-20 open Assets:Cash\n2023-05-21 close Assets:Cash\n");
+/// assert_eq!(parse.entries().len(), 2);
+///
+/// // Replace the second directive by two directives
+/// let change = parse.edit(28..56, "2023-05-21 open Assets:Bank\n2023-05-22 close Assets:Cash\n");
+/// assert_eq!(parse.entries().len(), 3);
+/// assert_eq!(change.removed, 1..2);
+/// assert_eq!(change.inserted, 1..3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncrementalParse<D> {
+    source: String,
+    items: Vec<Item>,
+    entries: Vec<Entry<D>>,
+    /// Offset and line number of the syntax error, if any
+    error: Option<(usize, u32)>,
+}
+
+/// Entries changed by an edit of an [`IncrementalParse`]
+///
+/// The entries at the `removed` indices of the entries before the edit were replaced
+/// by the entries at the `inserted` indices of the entries after the edit.
+/// The other entries are unchanged, except for their line numbers which are shifted
+/// by the number of lines added or removed by the edit.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Change {
+    /// Indices of the replaced entries, in the entries before the edit
+    pub removed: Range<usize>,
+    /// Indices of the new entries, in the entries after the edit
+    pub inserted: Range<usize>,
+}
+
+/// Part of the source parsed as one raw entry
+#[derive(Debug, Clone)]
+struct Item {
+    range: Range<usize>,
+    line_number: u32,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Entry,
+    PushTag(Tag),
+    PopTag(Tag),
+    Comment,
+}
+
+impl Kind {
+    fn of<D>(entry: &RawEntry<D>) -> Self {
+        match entry {
+            RawEntry::Directive(_) | RawEntry::Option(_) | RawEntry::Include(..) => Kind::Entry,
+            RawEntry::PushTag(tag) => Kind::PushTag(tag.clone()),
+            RawEntry::PopTag(tag) => Kind::PopTag(tag.clone()),
+            RawEntry::Comment => Kind::Comment,
+        }
+    }
+
+    fn apply_to(&self, tag_stack: &mut TagStack) {
+        match self {
+            Kind::PushTag(tag) => {
+                tag_stack.apply::<()>(RawEntry::PushTag(tag.clone()));
+            }
+            Kind::PopTag(tag) => {
+                tag_stack.apply::<()>(RawEntry::PopTag(tag.clone()));
+            }
+            Kind::Entry | Kind::Comment => (),
+        }
+    }
+}
+
+impl<D: Decimal> IncrementalParse<D> {
+    /// Parse the source
+    pub fn new(source: impl Into<String>) -> Self {
+        let mut parse = Self {
+            source: source.into(),
+            items: Vec::new(),
+            entries: Vec::new(),
+            error: None,
+        };
+        let parsed = parse.parse_from(0, 1, TagStack::default(), |_, _| false);
+        parse.items = parsed.items;
+        parse.entries = parsed.entries;
+        parse.error = parsed.error;
+        parse
+    }
+
+    /// Replace the `range` of the source by the `replacement`, and update the entries accordingly
+    ///
+    /// Returns which entries changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or does not lie on `char` boundaries, like [`String::replace_range`].
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Change {
+        let removed_lines = count_lines(&self.source[range.clone()]);
+        let added_lines = count_lines(replacement);
+        self.source.replace_range(range.clone(), replacement);
+        let new_offset = |offset: usize| offset - range.len() + replacement.len();
+        let new_line = |line: u32| line + added_lines - removed_lines;
+
+        // Start at the last item starting before the edit that cannot be part of a preceding entry
+        let start = self
+            .items
+            .iter()
+            .rposition(|item| {
+                item.range.start < range.start && is_anchor(&self.source[item.range.start..])
+            })
+            .unwrap_or(0);
+        let mut tag_stack = TagStack::default();
+        for item in &self.items[..start] {
+            item.kind.apply_to(&mut tag_stack);
+        }
+        let (offset, line_number) = self
+            .items
+            .get(start)
+            .map_or((0, 1), |item| (item.range.start, item.line_number));
+
+        // Parse until reaching the start of an item after the edit, with the same tags pushed as before the edit
+        let old_error = self.error;
+        let items = &self.items;
+        let mut old_tag_stack = tag_stack.clone();
+        let mut next_old_item = start;
+        let parsed = self.parse_from(offset, line_number, tag_stack, |offset, tag_stack| {
+            if offset < range.start + replacement.len() {
+                return false;
+            }
+            let old_offset = offset + range.len() - replacement.len();
+            while let Some(item) = items.get(next_old_item) {
+                if item.range.start >= old_offset {
+                    break;
+                }
+                item.kind.apply_to(&mut old_tag_stack);
+                next_old_item += 1;
+            }
+            let is_item_start = items
+                .get(next_old_item)
+                .map_or(false, |item| item.range.start == old_offset);
+            let is_error = old_error.map_or(false, |(offset, _)| offset == old_offset);
+            (is_item_start && *tag_stack == old_tag_stack) || is_error
+        });
+
+        let end = if parsed.is_resynchronized {
+            next_old_item
+        } else {
+            self.items.len()
+        };
+        let Parsed {
+            mut items,
+            mut entries,
+            error,
+            is_resynchronized,
+        } = parsed;
+
+        // The items ending before the edit are unchanged if they have the same range
+        let unchanged = items
+            .iter()
+            .zip(&self.items[start..end])
+            .take_while(|(new, old)| new.range == old.range && new.range.end <= range.start)
+            .count();
+        entries.drain(..entry_count(&items[..unchanged]));
+        items.drain(..unchanged);
+        let start = start + unchanged;
+
+        let first_entry = entry_count(&self.items[..start]);
+        let removed_entries = entry_count(&self.items[start..end]);
+        for item in &mut self.items[end..] {
+            item.range = new_offset(item.range.start)..new_offset(item.range.end);
+            item.line_number = new_line(item.line_number);
+        }
+        for entry in &mut self.entries[first_entry + removed_entries..] {
+            if let Entry::Directive(directive) = entry {
+                directive.line_number = new_line(directive.line_number);
+            }
+        }
+        let inserted_entries = entries.len();
+        self.items.splice(start..end, items);
+        self.entries
+            .splice(first_entry..first_entry + removed_entries, entries);
+        if !is_resynchronized {
+            self.error = error;
+        } else if let Some((offset, line)) = self.error {
+            self.error = Some((new_offset(offset), new_line(line)));
+        }
+        Change {
+            removed: first_entry..first_entry + removed_entries,
+            inserted: first_entry..first_entry + inserted_entries,
+        }
+    }
+
+    /// Returns the current source
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the entries found before the syntax error, if any
+    #[must_use]
+    pub fn entries(&self) -> &[Entry<D>] {
+        &self.entries
+    }
+
+    /// Returns the syntax error, if any
+    #[must_use]
+    pub fn error(&self) -> Option<Error> {
+        self.error
+            .map(|(offset, line_number)| Error::at(self.source.as_str(), offset, line_number))
+    }
+
+    /// Parse the source from the offset, until reaching the end of the input, an error,
+    /// or an offset for which `stop` returns true
+    fn parse_from(
+        &self,
+        mut offset: usize,
+        mut line_number: u32,
+        mut tag_stack: TagStack,
+        mut stop: impl FnMut(usize, &TagStack) -> bool,
+    ) -> Parsed<D> {
+        let mut parsed = Parsed {
+            items: Vec::new(),
+            entries: Vec::new(),
+            error: None,
+            is_resynchronized: false,
+        };
+        while offset < self.source.len() {
+            if stop(offset, &tag_stack) {
+                parsed.is_resynchronized = true;
+                return parsed;
+            }
+            let input = span(&self.source[offset..]);
+            let at = match crate::entry::<D>(input) {
+                Ok((rest, entry)) => {
+                    let end = offset + rest.location_offset();
+                    parsed.items.push(Item {
+                        range: offset..end,
+                        line_number,
+                        kind: Kind::of(&entry),
+                    });
+                    parsed
+                        .entries
+                        .extend(tag_stack.apply(entry.shift_lines(line_number - 1)));
+                    offset = end;
+                    line_number += rest.location_line() - 1;
+                    continue;
+                }
+                Err(nom::Err::Error(_) | nom::Err::Incomplete(_)) => input,
+                Err(nom::Err::Failure(err)) => err.input,
+            };
+            parsed.error = Some((
+                offset + at.location_offset(),
+                line_number + at.location_line() - 1,
+            ));
+            break;
+        }
+        parsed
+    }
+}
+
+struct Parsed<D> {
+    items: Vec<Item>,
+    entries: Vec<Entry<D>>,
+    error: Option<(usize, u32)>,
+    /// True if the parsing stopped at an offset for which `stop` returned true
+    is_resynchronized: bool,
+}
+
+fn entry_count(items: &[Item]) -> usize {
+    items
+        .iter()
+        .filter(|item| matches!(item.kind, Kind::Entry))
+        .count()
+}
+
+fn count_lines(text: &str) -> u32 {
+    u32::try_from(text.matches('\n').count()).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,160 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{Amount, Decimal, Transaction, WeightError};
+
+/// Error returned by [`Transaction::interpolate`] and [`Transaction::residual`]
+///
+/// The postings are identified by their index in [`Transaction::postings`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum InterpolationError {
+    /// The posting has a cost without amount (like `{}`)
+    ///
+    /// Its weight depends on the lots it is booked against.
+    IncompletePosting(usize),
+    /// More than one posting has no amount, so that the residual cannot be attributed to one of them
+    AmbiguousPostings(Vec<usize>),
+    /// The weight of the posting, or its sum with the weights of the previous postings, overflows
+    Overflow(usize),
+}
+
+impl Display for InterpolationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpolationError::IncompletePosting(index) => {
+                write!(f, "The posting {index} has a cost without amount")
+            }
+            InterpolationError::AmbiguousPostings(indices) => {
+                write!(f, "More than one posting without amount:")?;
+                for index in indices {
+                    write!(f, " {index}")?;
+                }
+                Ok(())
+            }
+            InterpolationError::Overflow(index) => {
+                write!(f, "The weight of the posting {index} overflows")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterpolationError {}
+
+impl<D: Decimal + PartialOrd + Default> Transaction<D> {
+    /// Returns the sum of the weights of the postings having an amount, per currency
+    ///
+    /// See [`Posting::weight`](crate::Posting::weight) for the definition of the weight of a posting.
+    ///
+    /// The currencies are listed in the order in which they first appear, including the ones summing up to zero.
+    ///
+    /// # Example
+    /// ```
+    /// # use beancount_parser::{BeancountFile, DirectiveContent};
+    /// let input = r#"
+    /// 2024-01-03 * "Exchange"
+    ///   Assets:Cash:EUR     10 EUR @ 1.10 CHF
+    ///   Assets:Cash:CHF    -10 CHF
+    /// "#;
+    /// let beancount: BeancountFile<f64> = input.parse().unwrap();
+    /// let DirectiveContent::Transaction(trx) = &beancount.directives[0].content else { unreachable!() };
+    /// let residual = trx.residual().unwrap();
+    /// assert_eq!(residual.len(), 1);
+    /// assert_eq!(residual[0].currency.as_str(), "CHF");
+    /// assert!((residual[0].value - 1.0).abs() < 1e-9);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InterpolationError::IncompletePosting`] if the weight of a posting cannot be computed,
+    /// and [`InterpolationError::Overflow`] if a weight or the sum of the weights overflows
+    pub fn residual(&self) -> Result<Vec<Amount<D>>, InterpolationError> {
+        let mut residual: Vec<Amount<D>> = Vec::new();
+        for (index, posting) in self.postings.iter().enumerate() {
+            if posting.amount.is_none() {
+                continue;
+            }
+            let weight = posting.weight().map_err(|err| match err {
+                WeightError::Overflow => InterpolationError::Overflow(index),
+                _ => InterpolationError::IncompletePosting(index),
+            })?;
+            match residual
+                .iter_mut()
+                .find(|amount| amount.currency == weight.currency)
+            {
+                Some(amount) => {
+                    amount.value = amount
+                        .value
+                        .clone()
+                        .checked_add(weight.value)
+                        .ok_or(InterpolationError::Overflow(index))?;
+                }
+                None => residual.push(weight),
+            }
+        }
+        Ok(residual)
+    }
+
+    /// Fill in the amount of the posting without amount, so that the transaction balances
+    ///
+    /// Like beancount, the posting without amount is replaced by one posting per currency of the [residual](Self::residual),
+    /// which receives the opposite of the residual in that currency. The currencies with a zero residual are skipped,
+    /// so that the posting is removed if the transaction already balances.
+    ///
+    /// The transaction is left unchanged if all the postings have an amount, or if an error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// # use beancount_parser::{BeancountFile, DirectiveContent};
+    /// let input = r#"
+    /// 2024-01-03 * "Groceries and fuel"
+    ///   Expenses:Food    10 CHF
+    ///   Expenses:Fuel    20 EUR
+    ///   Assets:Cash
+    /// "#;
+    /// let mut beancount: BeancountFile<f64> = input.parse().unwrap();
+    /// let DirectiveContent::Transaction(trx) = &mut beancount.directives[0].content else { unreachable!() };
+    /// trx.interpolate().unwrap();
+    /// assert_eq!(trx.postings.len(), 4);
+    /// assert_eq!(trx.postings[2].account.as_str(), "Assets:Cash");
+    /// assert_eq!(trx.postings[2].amount.as_ref().unwrap().value, -10.0);
+    /// assert_eq!(trx.postings[2].amount.as_ref().unwrap().currency.as_str(), "CHF");
+    /// assert_eq!(trx.postings[3].account.as_str(), "Assets:Cash");
+    /// assert_eq!(trx.postings[3].amount.as_ref().unwrap().value, -20.0);
+    /// assert_eq!(trx.postings[3].amount.as_ref().unwrap().currency.as_str(), "EUR");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InterpolationError::AmbiguousPostings`] if more than one posting has no amount,
+    /// and [`InterpolationError::IncompletePosting`] or [`InterpolationError::Overflow`] if the residual cannot be computed
+    pub fn interpolate(&mut self) -> Result<(), InterpolationError> {
+        let missing: Vec<usize> = self
+            .postings
+            .iter()
+            .enumerate()
+            .filter(|(_, posting)| posting.amount.is_none())
+            .map(|(index, _)| index)
+            .collect();
+        let index = match missing[..] {
+            [] => return Ok(()),
+            [index] => index,
+            _ => return Err(InterpolationError::AmbiguousPostings(missing)),
+        };
+        let residual = self.residual()?;
+        let posting = self.postings.remove(index);
+        let zero = D::default();
+        let filled = residual
+            .into_iter()
+            .filter(|amount| amount.value != zero)
+            .map(|amount| {
+                let mut filled = posting.clone();
+                filled.amount = Some(Amount {
+                    value: -amount.value,
+                    currency: amount.currency,
+                });
+                filled
+            });
+        self.postings.splice(index..index, filled);
+        Ok(())
+    }
+}
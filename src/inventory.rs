@@ -0,0 +1,283 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{amount::abs, Amount, ArithmeticError, Cost, Decimal, Posting};
+
+/// Units of a currency, optionally held at cost
+///
+/// See: <https://beancount.github.io/docs/beancount_design_doc.html#positions-and-inventories>
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Position<D> {
+    /// Number of units, and their currency
+    pub units: Amount<D>,
+    /// Cost of each unit, with the date and label of the lot
+    pub cost: Option<Cost<D>>,
+}
+
+impl<D> Position<D> {
+    /// Create a position
+    #[must_use]
+    pub fn new(units: Amount<D>, cost: Option<Cost<D>>) -> Self {
+        Self { units, cost }
+    }
+}
+
+/// Formats the position like beancount, for example `10 ACME {100 USD, 2024-01-01, "lot"}`
+impl<D: Display> Display for Position<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.units.value, self.units.currency)?;
+        let Some(cost) = &self.cost else {
+            return Ok(());
+        };
+        let mut parts = Vec::new();
+        if let Some(amount) = &cost.amount {
+            parts.push(format!("{} {}", amount.value, amount.currency));
+        }
+        if let Some(date) = cost.date {
+            parts.push(format!(
+                "{:04}-{:02}-{:02}",
+                date.year, date.month, date.day
+            ));
+        }
+        if let Some(label) = &cost.label {
+            parts.push(format!("\"{label}\""));
+        }
+        write!(f, " {{{}}}", parts.join(", "))
+    }
+}
+
+impl<D: Clone> Position<D> {
+    /// Returns the position added to (or removed from) the account of the posting
+    ///
+    /// Returns `None` if the posting has no amount.
+    #[must_use]
+    pub fn from_posting(posting: &Posting<D>) -> Option<Self> {
+        Some(Self::new(posting.amount.clone()?, posting.cost.clone()))
+    }
+}
+
+/// Positions held in an account
+///
+/// The positions are grouped in lots, one per currency and cost.
+/// The lots are kept in the order in which they were created, and the lots with zero units are removed.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{Amount, Cost, Inventory, Position};
+/// let amount = |value: f64, currency: &str| Amount { value, currency: currency.parse().unwrap() };
+/// let mut cost = Cost::default();
+/// cost.amount = Some(amount(100.0, "USD"));
+///
+/// let mut inventory = Inventory::new();
+/// inventory.add(Position::new(amount(10.0, "ACME"), Some(cost.clone()))).unwrap();
+/// inventory.add(Position::new(amount(50.0, "USD"), None)).unwrap();
+/// inventory.reduce(Position::new(amount(-4.0, "ACME"), Some(cost))).unwrap();
+/// assert_eq!(inventory.positions().len(), 2);
+/// assert_eq!(inventory.units("ACME"), Ok(6.0));
+/// assert_eq!(inventory.units("EUR"), Ok(0.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory<D> {
+    positions: Vec<Position<D>>,
+}
+
+impl<D> Default for Inventory<D> {
+    fn default() -> Self {
+        Self {
+            positions: Vec::new(),
+        }
+    }
+}
+
+impl<D> Inventory<D> {
+    /// Create an empty inventory
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the lots of the inventory, in the order in which they were created
+    #[must_use]
+    pub fn positions(&self) -> &[Position<D>] {
+        &self.positions
+    }
+
+    /// Returns true if the inventory holds no units
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns an iterator over the lots of the inventory
+    pub fn iter(&self) -> std::slice::Iter<'_, Position<D>> {
+        self.positions.iter()
+    }
+}
+
+impl<D: Decimal + PartialOrd + Default> Inventory<D> {
+    /// Add the units of the position to the lot having the same currency and cost, or create a new lot
+    ///
+    /// The units may be negative, and the lot is removed if it ends up with zero units.
+    /// Use [`Inventory::reduce`] to make sure that the reduced lot exists and holds enough units.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ArithmeticError`] if the units of the lot overflow, in which case the inventory is left unchanged
+    pub fn add(&mut self, position: Position<D>) -> Result<(), ArithmeticError> {
+        match self.lot_index(&position) {
+            Some(index) => self.add_to_lot(index, position.units.value)?,
+            None => {
+                if position.units.value != D::default() {
+                    self.positions.push(position);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reduce the lot having the same currency and cost as the position
+    ///
+    /// The units of the position must be of the opposite sign of the units of the lot,
+    /// like the units of a posting selling the lot.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReductionError::NoMatchingLot`] if there is no lot with the same currency and cost holding units of the opposite sign,
+    /// and [`ReductionError::InsufficientUnits`] if the lot holds fewer units than the reduction.
+    /// The inventory is left unchanged in case of error.
+    pub fn reduce(&mut self, position: Position<D>) -> Result<(), ReductionError<D>> {
+        let zero = D::default();
+        let lot_index = self.lot_index(&position);
+        let reduction = position.units.value;
+        let index = lot_index
+            .filter(|&index| (self.positions[index].units.value < zero) != (reduction < zero))
+            .ok_or(ReductionError::NoMatchingLot)?;
+        let lot = &self.positions[index];
+        if abs(reduction.clone()) > abs(lot.units.value.clone()) {
+            return Err(ReductionError::InsufficientUnits(lot.clone()));
+        }
+        self.add_to_lot(index, reduction)
+            .map_err(|ArithmeticError| ReductionError::Overflow)
+    }
+
+    /// Add all the positions of the other inventory
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ArithmeticError`] if the units of a lot overflow, in which case the inventory is left unchanged
+    pub fn merge(&mut self, other: Inventory<D>) -> Result<(), ArithmeticError> {
+        let mut merged = self.clone();
+        for position in other {
+            merged.add(position)?;
+        }
+        *self = merged;
+        Ok(())
+    }
+
+    /// Returns the total units of the currency, in all the lots
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ArithmeticError`] if the total overflows
+    pub fn units(&self, currency: &str) -> Result<D, ArithmeticError> {
+        self.positions
+            .iter()
+            .filter(|position| position.units.currency.as_str() == currency)
+            .try_fold(D::default(), |total, position| {
+                total.checked_add(position.units.value.clone())
+            })
+            .ok_or(ArithmeticError)
+    }
+
+    /// Returns the total units of each currency held, in the order in which they first appear
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ArithmeticError`] if a total overflows
+    pub fn totals(&self) -> Result<Vec<Amount<D>>, ArithmeticError> {
+        let mut totals: Vec<Amount<D>> = Vec::new();
+        for position in &self.positions {
+            match totals
+                .iter_mut()
+                .find(|total| total.currency == position.units.currency)
+            {
+                Some(total) => {
+                    total.value = total
+                        .value
+                        .clone()
+                        .checked_add(position.units.value.clone())
+                        .ok_or(ArithmeticError)?;
+                }
+                None => totals.push(position.units.clone()),
+            }
+        }
+        Ok(totals)
+    }
+
+    fn lot_index(&self, position: &Position<D>) -> Option<usize> {
+        self.positions.iter().position(|lot| {
+            lot.units.currency == position.units.currency && lot.cost == position.cost
+        })
+    }
+
+    fn add_to_lot(&mut self, index: usize, units: D) -> Result<(), ArithmeticError> {
+        let lot = &mut self.positions[index];
+        lot.units.value = lot
+            .units
+            .value
+            .clone()
+            .checked_add(units)
+            .ok_or(ArithmeticError)?;
+        if lot.units.value == D::default() {
+            self.positions.remove(index);
+        }
+        Ok(())
+    }
+}
+
+impl<D> IntoIterator for Inventory<D> {
+    type Item = Position<D>;
+    type IntoIter = std::vec::IntoIter<Position<D>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.positions.into_iter()
+    }
+}
+
+impl<'a, D> IntoIterator for &'a Inventory<D> {
+    type Item = &'a Position<D>;
+    type IntoIter = std::slice::Iter<'a, Position<D>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.positions.iter()
+    }
+}
+
+/// Error returned by [`Inventory::reduce`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ReductionError<D> {
+    /// There is no lot with the same currency and cost holding units of the opposite sign
+    NoMatchingLot,
+    /// The lot holds fewer units than the reduction
+    ///
+    /// Contains the lot.
+    InsufficientUnits(Position<D>),
+    /// The remaining units of the lot cannot be computed (see [`Decimal::checked_add`])
+    Overflow,
+}
+
+impl<D: Display> Display for ReductionError<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReductionError::NoMatchingLot => write!(f, "No matching lot"),
+            ReductionError::InsufficientUnits(lot) => write!(
+                f,
+                "Not enough units in the lot: {} {}",
+                lot.units.value, lot.units.currency
+            ),
+            ReductionError::Overflow => write!(f, "Arithmetic overflow"),
+        }
+    }
+}
+
+impl<D: std::fmt::Debug + Display> std::error::Error for ReductionError<D> {}
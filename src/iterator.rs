@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use nom::{combinator::ParserIterator, Finish};
+use nom::{combinator::ParserIterator, error::ErrorKind, Finish};
 
 use crate::{DirectiveContent, Entry, Error, RawEntry, Span, Tag};
 
@@ -9,7 +9,9 @@ type InnerIter<'i, F> = ParserIterator<Span<'i>, nom::error::Error<Span<'i>>, F>
 pub(crate) struct Iter<'i, F> {
     source: &'i str,
     inner: Option<InnerIter<'i, F>>,
-    tag_stack: HashSet<Tag>,
+    tag_stack: TagStack,
+    include_line_number: u32,
+    error_kind: Option<ErrorKind>,
 }
 
 impl<'i, F> Iter<'i, F> {
@@ -17,9 +19,21 @@ impl<'i, F> Iter<'i, F> {
         Self {
             source,
             inner: Some(value),
-            tag_stack: HashSet::new(),
+            tag_stack: TagStack::default(),
+            include_line_number: 0,
+            error_kind: None,
         }
     }
+
+    /// Line number of the last include entry returned
+    pub(crate) fn include_line_number(&self) -> u32 {
+        self.include_line_number
+    }
+
+    /// Kind of the parse error returned, if any
+    pub(crate) fn error_kind(&self) -> Option<ErrorKind> {
+        self.error_kind
+    }
 }
 
 impl<'i, D, F> Iterator for Iter<'i, F>
@@ -30,33 +44,51 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let inner = self.inner.as_mut()?;
         for entry in inner {
-            match entry {
-                RawEntry::Directive(mut d) => {
-                    if let DirectiveContent::Transaction(trx) = &mut d.content {
-                        trx.tags.extend(self.tag_stack.iter().cloned());
-                    }
-                    return Some(Ok(Entry::Directive(d)));
-                }
-                RawEntry::Option(o) => {
-                    return Some(Ok(Entry::Option(o)));
-                }
-                RawEntry::Include(path) => {
-                    return Some(Ok(Entry::Include(path)));
-                }
-                RawEntry::PushTag(tag) => {
-                    self.tag_stack.insert(tag);
-                }
-                RawEntry::PopTag(tag) => {
-                    self.tag_stack.remove(&tag);
-                }
-                RawEntry::Comment => (),
+            if let RawEntry::Include(_, line_number) = &entry {
+                self.include_line_number = *line_number;
+            }
+            if let Some(entry) = self.tag_stack.apply(entry) {
+                return Some(Ok(entry));
             }
         }
         match self.inner.take().unwrap().finish().finish() {
             Ok((rest, ())) if rest.fragment().is_empty() => None,
-            Ok((input, ())) | Err(nom::error::Error { input, .. }) => {
+            Ok((input, ())) => Some(Err(Error::new(self.source, input))),
+            Err(nom::error::Error { input, code }) => {
+                self.error_kind = Some(code);
                 Some(Err(Error::new(self.source, input)))
             }
         }
     }
 }
+
+/// Tags pushed by `pushtag` and not popped yet
+#[derive(Default, Clone, PartialEq)]
+pub(crate) struct TagStack(HashSet<Tag>);
+
+impl TagStack {
+    /// Update the stack with the raw entry, and returns the corresponding entry, if any
+    ///
+    /// The tags of the stack are added to the transactions.
+    pub(crate) fn apply<D>(&mut self, entry: RawEntry<D>) -> Option<Entry<D>> {
+        match entry {
+            RawEntry::Directive(mut d) => {
+                if let DirectiveContent::Transaction(trx) = &mut d.content {
+                    trx.tags.extend(self.0.iter().cloned());
+                }
+                Some(Entry::Directive(d))
+            }
+            RawEntry::Option(o) => Some(Entry::Option(o)),
+            RawEntry::Include(path, _) => Some(Entry::Include(path)),
+            RawEntry::PushTag(tag) => {
+                self.0.insert(tag);
+                None
+            }
+            RawEntry::PopTag(tag) => {
+                self.0.remove(&tag);
+                None
+            }
+            RawEntry::Comment => None,
+        }
+    }
+}
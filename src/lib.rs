@@ -5,7 +5,8 @@
 //! Use [`parse`] to get an instance of [`BeancountFile`].
 //!
 //! This is generic over the decimal type. The examples use `f64` as a decimal type.
-//! You may also use `Decimal` from the [rust_decimal crate].
+//! You may also use `Decimal` from the [rust_decimal crate] (with the `rust_decimal` feature).
+//! Wrap the decimal type in [`Number`] to also keep track of the precision with which each number was written.
 //!
 //! [rust_decimal crate]: https://docs.rs/rust_decimal
 //!
@@ -40,11 +41,10 @@
 //! ```
 
 use std::{
-    collections::HashSet,
-    fs::File,
-    io::Read,
+    io::BufRead,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
 use nom::{
@@ -58,17 +58,26 @@ use nom::{
 use nom_locate::position;
 
 pub use crate::{
-    account::{Account, Balance, Close, Open, Pad},
-    amount::{Amount, Currency, Decimal, Price},
+    account::{Account, Balance, BookingMethod, Close, Open, Pad},
+    amount::{Amount, ArithmeticError, Currency, Decimal, Price},
+    booking::{BookingEngine, BookingError, UnknownBookingMethod},
     date::Date,
-    error::{ConversionError, Error},
+    error::{ConversionError, Error, ReadFileErrorKind, ReadFileErrorV2},
     event::Event,
-    transaction::{Cost, Link, Posting, PostingPrice, Tag, Transaction},
-};
-use crate::{
-    error::{ReadFileErrorContent, ReadFileErrorV2},
-    iterator::Iter,
+    include_graph::{Include, IncludeDiagnostic, IncludeGraph},
+    incremental::{Change, IncrementalParse},
+    interpolation::InterpolationError,
+    inventory::{Inventory, Position, ReductionError},
+    loader::{IncludeOrder, Limits, ReadOptions},
+    number::Number,
+    tolerance::{Residual, Tolerances, TransactionBalanceError},
+    transaction::{Cost, Link, Posting, PostingPrice, Tag, Transaction, WeightError},
+    validation::{
+        check_accounts, check_balances, expand_pads, sort_directives, AccountError,
+        AccountErrorKind, BalanceError, PadError,
+    },
 };
+use crate::{error::ReadFileErrorContent, iterator::Iter};
 
 #[deprecated(note = "use `metadata::Value` instead", since = "1.0.0-beta.3")]
 #[doc(hidden)]
@@ -76,15 +85,28 @@ pub type MetadataValue<D> = metadata::Value<D>;
 
 mod account;
 mod amount;
+mod booking;
 mod date;
 mod error;
 mod event;
+pub mod fs;
+mod include_graph;
+mod incremental;
+mod interpolation;
+mod inventory;
 mod iterator;
+mod loader;
 pub mod metadata;
+mod number;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod reader;
+mod tolerance;
 mod transaction;
 #[cfg(feature = "unstable-v3")]
 #[allow(missing_docs, dead_code)]
 pub mod v3;
+mod validation;
 
 /// Parse the input beancount file and return an instance of [`BeancountFile`] on success
 ///
@@ -111,7 +133,60 @@ pub fn parse<D: Decimal>(input: &str) -> Result<BeancountFile<D>, Error> {
 pub fn parse_iter<'a, D: Decimal + 'a>(
     input: &'a str,
 ) -> impl Iterator<Item = Result<Entry<D>, Error>> + 'a {
-    Iter::new(input, iterator(Span::new(input), entry::<D>))
+    Iter::new(input, iterator(span(input), entry::<D>))
+}
+
+/// Parse the input in chunks on multiple threads, and returns a [`BeancountFile`]
+///
+/// The input is split at lines starting with a digit (the date of a directive), so that the chunks can be parsed in parallel.
+/// The `pushtag`/`poptag` directives and the line numbers are then reconciled, so that the result is the same as with [`parse`].
+/// It is only worth it for large inputs, smaller inputs are parsed on the current thread.
+///
+/// Requires the `parallel` feature.
+///
+/// # Example
+///
+/// ```
+/// let input = std::fs::read_to_string("tests/samples/official.beancount").unwrap();
+/// let beancount = beancount_parser::parse_parallel::<f64>(&input).unwrap();
+/// assert_eq!(beancount.directives.len(), 1714);
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`Error`] in case of invalid beancount syntax found.
+///
+/// # Panics
+///
+/// Panics if the decimal type panics when parsing a number
+#[cfg(feature = "parallel")]
+pub fn parse_parallel<D: Decimal + Send>(input: &str) -> Result<BeancountFile<D>, Error> {
+    parallel::parse_in_chunks(input).map(|entries| entries.into_iter().collect())
+}
+
+/// Parse the beancount entries read from the reader, and return an iterator over `Result<Entry<D>, Error>`
+///
+/// Unlike [`parse_iter`], the input does not need to be held in memory:
+/// the lines are read as the entries are consumed, and only the lines of the entry being parsed are kept in memory.
+/// The entries are the same as with [`parse_iter`].
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{parse_reader, Entry};
+/// let input = "2023-05-20 open Assets:Cash\n2023-05-21 close Assets:Cash\n";
+/// let entries: Vec<Entry<f64>> = parse_reader(input.as_bytes()).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(entries.len(), 2);
+/// ```
+///
+/// # Errors
+///
+/// The iterator will emit an [`Error`] in case of invalid beancount syntax found,
+/// or if the input cannot be read (see [`Error::io_error`]). It ends after the first error.
+pub fn parse_reader<D: Decimal, R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<Entry<D>, Error>> {
+    reader::ReaderIter::new(reader)
 }
 
 impl<D: Decimal> FromStr for BeancountFile<D> {
@@ -141,6 +216,9 @@ pub fn read_files<D: Decimal, F: FnMut(Entry<D>)>(
     read_files_v2(files, on_entry).map_err(|err| match err.error {
         ReadFileErrorContent::Io(err) => error::ReadFileError::Io(err),
         ReadFileErrorContent::Syntax(err) => error::ReadFileError::Syntax(err),
+        ReadFileErrorContent::OutsideRoot(_) | ReadFileErrorContent::Limit(_) => {
+            unreachable!("there is no root nor limit by default")
+        }
     })
 }
 
@@ -170,54 +248,189 @@ pub fn read_files_to_vec<D: Decimal>(
 /// or if there is a beancount syntax error in any file read
 pub fn read_files_v2<D: Decimal, F: FnMut(Entry<D>)>(
     files: impl IntoIterator<Item = PathBuf>,
-    mut on_entry: F,
+    on_entry: F,
 ) -> Result<(), ReadFileErrorV2> {
-    let mut loaded: HashSet<PathBuf> = HashSet::new();
-    let mut pending: Vec<PathBuf> = files
-        .into_iter()
-        .map(|p| {
-            p.canonicalize()
-                .map_err(|err| ReadFileErrorV2::from_io(p, err))
-        })
-        .collect::<Result<_, _>>()?;
-    let mut buffer = String::new();
-    while let Some(path) = pending.pop() {
-        if loaded.contains(&path) {
-            continue;
-        }
-        loaded.insert(path.clone());
-        buffer.clear();
-        File::open(&path)
-            .and_then(|mut f| f.read_to_string(&mut buffer))
-            .map_err(|err| ReadFileErrorV2::from_io(path.clone(), err))?;
-        for result in parse_iter::<D>(&buffer) {
-            let entry = match result {
-                Ok(entry) => entry,
-                Err(err) => return Err(ReadFileErrorV2::from_syntax(path, err)),
-            };
-            match entry {
-                Entry::Include(include) => {
-                    let path = if include.is_relative() {
-                        let Some(parent) = path.parent() else {
-                            unreachable!("there must be a parent if the file was valid")
-                        };
-                        parent.join(&include)
-                    } else {
-                        include
-                    };
-                    let path = path
-                        .canonicalize()
-                        .map_err(|err| ReadFileErrorV2::from_io(path, err))?;
-                    if !loaded.contains(&path) {
-                        pending.push(path.clone());
-                    }
-                    on_entry(Entry::Include(path));
-                }
-                entry => on_entry(entry),
-            }
-        }
-    }
-    Ok(())
+    loader::read_files(&fs::StdFileSystem, files, &ReadOptions::default(), on_entry).map(|_| ())
+}
+
+/// Read the files from disk and parse their content, using the given [`ReadOptions`].
+///
+/// It follows the `include` directives found, and returns their [`IncludeGraph`].
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{read_files_with_options, BeancountFile, ReadOptions};
+/// let mut options = ReadOptions::default();
+/// options.limits.max_file_size = Some(1024 * 1024);
+/// options.limits.max_expression_depth = Some(10);
+/// let mut file = BeancountFile::<f64>::default();
+/// read_files_with_options(["tests/samples/simple.beancount".into()], &options, |entry| {
+///     file.extend(Some(entry));
+/// })
+/// .unwrap();
+/// assert_eq!(file.directives.len(), 16);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if any file could not be read (IO error),
+/// if there is a beancount syntax error in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+pub fn read_files_with_options<D: Decimal, F: FnMut(Entry<D>)>(
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_files(&fs::StdFileSystem, files, options, on_entry)
+}
+
+/// Read the files from the given [`FileSystem`](fs::FileSystem) and parse their content, using the given [`ReadOptions`].
+///
+/// It follows the `include` directives found, and returns their [`IncludeGraph`].
+///
+/// See the [`fs`] module for an example.
+///
+/// # Errors
+///
+/// Returns an error if any file could not be read (IO error),
+/// if there is a beancount syntax error in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+pub fn read_files_from<D: Decimal, F: FnMut(Entry<D>)>(
+    fs: &(impl fs::FileSystem + ?Sized),
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_files(fs, files, options, on_entry)
+}
+
+/// Read the files from disk and parse their content, using the given [`ReadOptions`],
+/// while the included files are read and parsed in parallel.
+///
+/// The entries are passed to `on_entry` in the same order as with [`read_files_with_options`],
+/// and the same error is returned if there is any.
+///
+/// Requires the `parallel` feature.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{read_files_parallel, BeancountFile, ReadOptions};
+/// let mut file = BeancountFile::<f64>::default();
+/// read_files_parallel(["tests/samples/includes.beancount".into()], &ReadOptions::default(), |entry| {
+///     file.extend(Some(entry));
+/// })
+/// .unwrap();
+/// assert_eq!(file.directives.len(), 1731);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if any file could not be read (IO error),
+/// if there is a beancount syntax error in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+///
+/// # Panics
+///
+/// Panics if parsing a file panics in one of the threads, which may happen if the decimal type panics.
+#[cfg(feature = "parallel")]
+pub fn read_files_parallel<D: Decimal + Send, F: FnMut(Entry<D>)>(
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_files_parallel(&fs::StdFileSystem, files, options, on_entry)
+}
+
+/// Read the files from the given [`FileSystem`](fs::FileSystem) and parse their content, using the given [`ReadOptions`],
+/// while the included files are read and parsed in parallel.
+///
+/// See [`read_files_parallel`] and [`read_files_from`]
+///
+/// Requires the `parallel` feature.
+///
+/// # Errors
+///
+/// Returns an error if any file could not be read (IO error),
+/// if there is a beancount syntax error in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+///
+/// # Panics
+///
+/// Panics if parsing a file panics in one of the threads, which may happen if the decimal type panics.
+#[cfg(feature = "parallel")]
+pub fn read_files_parallel_from<D: Decimal + Send, F: FnMut(Entry<D>)>(
+    fs: &(impl fs::FileSystem + Sync + ?Sized),
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_files_parallel(fs, files, options, on_entry)
+}
+
+/// Parse the source of a beancount file located at `path`, and follow the `include` directives found from there.
+///
+/// Returns the [`IncludeGraph`] of the `include` directives found.
+///
+/// The file at `path` does not have to exist, but its directory does, as the relative includes are resolved from it.
+/// This is useful when the content of the main file comes from elsewhere (like a network request),
+/// while the included files are on disk.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{read_str_with_options, BeancountFile, ReadOptions};
+/// let source = "include \"simple.beancount\"\n2024-01-01 open Assets:Cash";
+/// let mut file = BeancountFile::<f64>::default();
+/// read_str_with_options(
+///     source,
+///     "tests/samples/uploaded.beancount".as_ref(),
+///     &ReadOptions::default(),
+///     |entry| file.extend(Some(entry)),
+/// )
+/// .unwrap();
+/// assert_eq!(file.directives.len(), 17);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if any included file could not be read (IO error),
+/// if there is a beancount syntax error in the source or in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+pub fn read_str_with_options<D: Decimal, F: FnMut(Entry<D>)>(
+    source: &str,
+    path: &Path,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_str(&fs::StdFileSystem, source, path, options, on_entry)
+}
+
+/// Parse the source of a beancount file located at `path`, and follow the `include` directives found
+/// in the given [`FileSystem`](fs::FileSystem).
+///
+/// See [`read_str_with_options`] and [`read_files_from`]
+///
+/// # Errors
+///
+/// Returns an error if any included file could not be read (IO error),
+/// if there is a beancount syntax error in the source or in any file read,
+/// if an included file is outside of the [`ReadOptions::root`] directory
+/// or if any of the [`Limits`] is exceeded
+pub fn read_str_from<D: Decimal, F: FnMut(Entry<D>)>(
+    fs: &(impl fs::FileSystem + ?Sized),
+    source: &str,
+    path: &Path,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    loader::read_str(fs, source, path, options, on_entry)
 }
 
 /// Main struct representing a parsed beancount file.
@@ -280,6 +493,34 @@ impl<D> BeancountFile<D> {
             .find(|opt| opt.name == key)
             .map(|opt| &opt.value[..])
     }
+
+    /// Returns the default booking method of the accounts, defined by the `booking_method` option
+    ///
+    /// Returns [`BookingMethod::Strict`] if the option is not set, and [`BookingMethod::Other`] if its value
+    /// is not a known booking method (see [`BookingEngine::try_from_options`] to report it).
+    ///
+    /// See: <https://beancount.github.io/docs/beancount_options_reference.html>
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use beancount_parser::{BeancountFile, BookingMethod};
+    /// let beancount: BeancountFile<f64> = r#"option "booking_method" "FIFO""#.parse().unwrap();
+    /// assert_eq!(beancount.booking_method(), BookingMethod::Fifo);
+    /// let beancount: BeancountFile<f64> = "".parse().unwrap();
+    /// assert_eq!(beancount.booking_method(), BookingMethod::Strict);
+    /// let beancount: BeancountFile<f64> = r#"option "booking_method" "FIFOO""#.parse().unwrap();
+    /// assert_eq!(beancount.booking_method(), BookingMethod::Other("FIFOO".into()));
+    /// ```
+    #[must_use]
+    pub fn booking_method(&self) -> BookingMethod {
+        self.options
+            .iter()
+            .rev()
+            .find(|opt| opt.name == "booking_method")
+            .map(|opt| BookingMethod::from(opt.value.as_str()))
+            .unwrap_or_default()
+    }
 }
 
 impl<D: Decimal> BeancountFile<D> {
@@ -342,7 +583,7 @@ impl<D> FromIterator<Entry<D>> for BeancountFile<D> {
 ///    }
 /// }
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct Directive<D> {
     /// Date of the directive
@@ -355,12 +596,27 @@ pub struct Directive<D> {
     pub metadata: metadata::Map<D>,
     /// Line number where the directive was found in the input file
     pub line_number: u32,
+    /// File where the directive was found
+    ///
+    /// It is set by the functions reading files, like [`read_files_v2`], and `None` when parsing a string.
+    ///
+    /// It is ignored when comparing directives, so that a directive read from a file is equal to the same directive parsed from a string.
+    pub file: Option<Arc<Path>>,
+}
+
+impl<D: PartialEq> PartialEq for Directive<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.date == other.date
+            && self.content == other.content
+            && self.metadata == other.metadata
+            && self.line_number == other.line_number
+    }
 }
 
 impl<D: Decimal> FromStr for Directive<D> {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match all_consuming(directive).parse(Span::new(s)).finish() {
+        match all_consuming(directive).parse(span(s)).finish() {
             Ok((_, d)) => Ok(d),
             Err(err) => Err(Error::new(s, err.input)),
         }
@@ -448,7 +704,41 @@ impl<D> DirectiveContent<D> {
     }
 }
 
-type Span<'a> = nom_locate::LocatedSpan<&'a str>;
+type Span<'a> = nom_locate::LocatedSpan<&'a str, State>;
+
+/// Default maximum nesting depth of parentheses in arithmetic expressions
+const DEFAULT_MAX_EXPRESSION_DEPTH: usize = 64;
+
+/// State of the parser, carried along with the input
+#[derive(Debug, Clone, Copy)]
+struct State {
+    /// Number of nested parentheses that an expression may still open
+    expression_depth: usize,
+}
+
+impl State {
+    fn new(max_expression_depth: usize) -> Self {
+        Self {
+            expression_depth: max_expression_depth,
+        }
+    }
+
+    /// Returns the state inside a pair of parentheses, or `None` if the expression is nested too deep
+    fn enter_group(self) -> Option<Self> {
+        Some(Self::new(self.expression_depth.checked_sub(1)?))
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_EXPRESSION_DEPTH)
+    }
+}
+
+/// Returns the input to parse, with the default state
+fn span(input: &str) -> Span<'_> {
+    Span::new_extra(input, State::default())
+}
 type IResult<'a, O> = nom::IResult<Span<'a>, O>;
 
 /// Entry in the beancount syntax
@@ -456,7 +746,7 @@ type IResult<'a, O> = nom::IResult<Span<'a>, O>;
 /// It is more general than `Directive` as an entry can also be option or an include.
 #[allow(missing_docs)]
 #[non_exhaustive]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Entry<D> {
     Directive(Directive<D>),
     Option(BeanOption),
@@ -491,16 +781,30 @@ impl<D> Entry<D> {
 enum RawEntry<D> {
     Directive(Directive<D>),
     Option(BeanOption),
-    Include(PathBuf),
+    Include(PathBuf, u32),
     PushTag(Tag),
     PopTag(Tag),
     Comment,
 }
 
+impl<D> RawEntry<D> {
+    /// Add `shift` to the line numbers, for entries parsed from a part of the input starting at line `shift + 1`
+    fn shift_lines(self, shift: u32) -> Self {
+        match self {
+            RawEntry::Directive(mut directive) => {
+                directive.line_number += shift;
+                RawEntry::Directive(directive)
+            }
+            RawEntry::Include(path, line_number) => RawEntry::Include(path, line_number + shift),
+            entry => entry,
+        }
+    }
+}
+
 /// An beancount option
 ///
 /// See: <https://beancount.github.io/docs/beancount_language_syntax.html#options>
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
 pub struct BeanOption {
     /// Name of the option
@@ -513,7 +817,7 @@ fn entry<D: Decimal>(input: Span<'_>) -> IResult<'_, RawEntry<D>> {
     alt((
         directive.map(RawEntry::Directive),
         option.map(|(name, value)| RawEntry::Option(BeanOption { name, value })),
-        include.map(|p| RawEntry::Include(p)),
+        (position, include).map(|(position, p)| RawEntry::Include(p, position.location_line())),
         tag_stack_operation,
         line.map(|()| RawEntry::Comment),
     ))
@@ -573,6 +877,7 @@ fn directive<D: Decimal>(input: Span<'_>) -> IResult<'_, Directive<D>> {
             content,
             metadata,
             line_number: position.location_line(),
+            file: None,
         },
     ))
 }
@@ -0,0 +1,611 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use nom::error::ErrorKind;
+
+use crate::{
+    error::{LimitExceeded, ReadFileErrorV2},
+    fs::{normalize, FileSystem},
+    include_graph::{Include, IncludeGraph},
+    iterator::Iter,
+    Decimal, Entry, Span, State, DEFAULT_MAX_EXPRESSION_DEPTH,
+};
+
+/// Options for reading files with [`read_files_with_options`](crate::read_files_with_options)
+/// or [`read_files_from`](crate::read_files_from)
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::ReadOptions;
+/// let mut options = ReadOptions::default();
+/// options.limits.max_included_files = Some(10);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ReadOptions {
+    /// Limits on the resources that may be used when reading the files
+    pub limits: Limits,
+    /// Directory to which the `include` directives are restricted
+    ///
+    /// Included files must be located within this directory once their path is canonicalized,
+    /// so that neither `..` components nor symbolic links can escape it.
+    /// An `include` directive whose path (or the part of its glob pattern before the first wildcard)
+    /// is outside of the directory is rejected before accessing the file system,
+    /// so that the errors do not reveal which files exist outside of it.
+    ///
+    /// The files explicitly passed to the read functions are not subject to this restriction.
+    pub root: Option<PathBuf>,
+    /// Order in which the entries of the included files are emitted
+    pub include_order: IncludeOrder,
+}
+
+/// Order in which the entries of included files are emitted
+///
+/// Each file is read only once, even if it is included multiple times.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum IncludeOrder {
+    /// The entries of the included files are emitted after the entries of the including file
+    #[default]
+    Deferred,
+    /// The entries of each included file are emitted right after its `include` directive,
+    /// depth-first and in declaration order, as beancount does.
+    Source,
+}
+
+/// Limits on the resources that may be used when reading files
+///
+/// All limits but the expression depth are disabled by default. Enable them when reading untrusted input,
+/// so that malicious files produce an error instead of exhausting the stack or the memory.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::Limits;
+/// let mut limits = Limits::default();
+/// limits.max_file_size = Some(1024 * 1024);
+/// limits.max_included_files = Some(10);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Maximum nesting depth of parentheses in arithmetic expressions
+    ///
+    /// When `None`, the default of 64 levels applies, and a deeper expression is a syntax error, like for the other parse functions.
+    pub max_expression_depth: Option<usize>,
+    /// Maximum size of a file, in bytes
+    pub max_file_size: Option<u64>,
+    /// Maximum number of files loaded by following `include` directives
+    pub max_included_files: Option<usize>,
+    /// Maximum length of a line, in bytes
+    pub max_line_length: Option<usize>,
+}
+
+pub(crate) fn read_files<D: Decimal, F: FnMut(Entry<D>)>(
+    fs: &(impl FileSystem + ?Sized),
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    let files = canonicalize_files(fs, files)?;
+    follow_includes(Loader::new(fs, options)?, files, on_entry, &mut ())
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn read_files_parallel<D: Decimal + Send, F: FnMut(Entry<D>)>(
+    fs: &(impl FileSystem + Sync + ?Sized),
+    files: impl IntoIterator<Item = PathBuf>,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    let files = canonicalize_files(fs, files)?;
+    let loader = Loader::new(fs, options)?;
+    crate::parallel::with_prefetcher(fs, &options.limits, |prefetcher| {
+        follow_includes(loader, files, on_entry, prefetcher)
+    })
+}
+
+pub(crate) fn read_str<D: Decimal, F: FnMut(Entry<D>)>(
+    fs: &(impl FileSystem + ?Sized),
+    source: &str,
+    path: &Path,
+    options: &ReadOptions,
+    on_entry: F,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    let path = virtual_path(fs, path)?;
+    follow_includes(
+        Loader::new(fs, options)?,
+        vec![(path, Some(source))],
+        on_entry,
+        &mut (),
+    )
+}
+
+fn canonicalize_files(
+    fs: &(impl FileSystem + ?Sized),
+    files: impl IntoIterator<Item = PathBuf>,
+) -> Result<Vec<(PathBuf, Option<&'static str>)>, ReadFileErrorV2> {
+    files
+        .into_iter()
+        .map(|p| match fs.canonicalize(&p) {
+            Ok(path) => Ok((path, None)),
+            Err(err) => Err(ReadFileErrorV2::from_io(p, err)),
+        })
+        .collect()
+}
+
+/// Returns the canonical form of a path that may not exist in the file system, as long as its parent does
+fn virtual_path(fs: &(impl FileSystem + ?Sized), path: &Path) -> Result<PathBuf, ReadFileErrorV2> {
+    let err = match fs.canonicalize(path) {
+        Ok(path) => return Ok(path),
+        Err(err) => err,
+    };
+    let Some(file_name) = path.file_name() else {
+        return Err(ReadFileErrorV2::from_io(path.into(), err));
+    };
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    match fs.canonicalize(parent) {
+        Ok(parent) => Ok(parent.join(file_name)),
+        Err(err) => Err(ReadFileErrorV2::from_io(path.into(), err)),
+    }
+}
+
+/// Entries of a file parsed ahead of time
+///
+/// Each entry comes with the line number of its directive, which is only meaningful for the include entries.
+/// The error, if any, was found after the entries.
+pub(crate) struct Parsed<D> {
+    pub(crate) entries: Vec<(Entry<D>, u32)>,
+    pub(crate) error: Option<ReadFileErrorV2>,
+}
+
+#[cfg(feature = "parallel")]
+impl<D: Decimal> Parsed<D> {
+    pub(crate) fn parse(fs: &(impl FileSystem + ?Sized), path: &Path, limits: &Limits) -> Self {
+        let mut entries = Vec::new();
+        let result = parse_file(fs, path, None, limits, &mut String::new(), |entry, line| {
+            entries.push((entry, line));
+            Ok(())
+        });
+        Self {
+            entries,
+            error: result.err(),
+        }
+    }
+}
+
+/// Source of the files parsed ahead of time
+pub(crate) trait Prefetch<D> {
+    /// Start parsing the included file, if possible
+    fn request(&mut self, path: &Path);
+
+    /// Returns the entries of the file, if it was requested
+    fn take(&mut self, path: &Path) -> Option<Parsed<D>>;
+}
+
+/// No file is parsed ahead of time
+impl<D> Prefetch<D> for () {
+    fn request(&mut self, _: &Path) {}
+
+    fn take(&mut self, _: &Path) -> Option<Parsed<D>> {
+        None
+    }
+}
+
+/// Read the files and the files they include, in the order defined by the options
+///
+/// Each file is given with its canonical path, and its source if it is already in memory.
+/// The included files are requested from `prefetch` as soon as they are found.
+///
+/// Returns the graph of the `include` directives found
+fn follow_includes<D: Decimal, Fs: FileSystem + ?Sized>(
+    mut loader: Loader<'_, Fs>,
+    files: Vec<(PathBuf, Option<&str>)>,
+    mut on_entry: impl FnMut(Entry<D>),
+    prefetch: &mut impl Prefetch<D>,
+) -> Result<IncludeGraph, ReadFileErrorV2> {
+    match loader.options.include_order {
+        IncludeOrder::Deferred => {
+            let mut pending: Vec<(PathBuf, Option<&str>, bool)> = files
+                .into_iter()
+                .map(|(path, source)| (path, source, false))
+                .collect();
+            while let Some((path, source, is_included)) = pending.pop() {
+                if loader.is_loaded(&path) {
+                    continue;
+                }
+                let prefetched = source.is_none().then(|| prefetch.take(&path)).flatten();
+                loader.load(&path, source, is_included, prefetched, |entry| {
+                    if let Entry::Include(path) = &entry {
+                        prefetch.request(path);
+                        pending.push((path.clone(), None, true));
+                    }
+                    on_entry(entry);
+                })?;
+            }
+        }
+        IncludeOrder::Source => {
+            let mut load =
+                |loader: &mut Loader<'_, Fs>, path: &Path, source: Option<&str>, is_included| {
+                    let prefetched = source.is_none().then(|| prefetch.take(path)).flatten();
+                    let entries = loader.load_to_vec(path, source, is_included, prefetched)?;
+                    for entry in &entries {
+                        if let Entry::Include(path) = entry {
+                            prefetch.request(path);
+                        }
+                    }
+                    Ok::<_, ReadFileErrorV2>(entries.into_iter())
+                };
+            for (path, source) in files {
+                if loader.is_loaded(&path) {
+                    continue;
+                }
+                let mut stack = vec![load(&mut loader, &path, source, false)?];
+                while let Some(entries) = stack.last_mut() {
+                    match entries.next() {
+                        Some(Entry::Include(path)) => {
+                            let is_new = !loader.is_loaded(&path);
+                            on_entry(Entry::Include(path.clone()));
+                            if is_new {
+                                stack.push(load(&mut loader, &path, None, true)?);
+                            }
+                        }
+                        Some(entry) => on_entry(entry),
+                        None => {
+                            stack.pop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(loader.graph)
+}
+
+/// State of the files being read
+struct Loader<'o, Fs: ?Sized> {
+    fs: &'o Fs,
+    options: &'o ReadOptions,
+    root: Option<PathBuf>,
+    loaded: HashSet<PathBuf>,
+    included_count: usize,
+    buffer: String,
+    graph: IncludeGraph,
+    /// The `include` directive through which each included file was first found
+    origins: HashMap<PathBuf, Include>,
+}
+
+impl<'o, Fs: FileSystem + ?Sized> Loader<'o, Fs> {
+    fn new(fs: &'o Fs, options: &'o ReadOptions) -> Result<Self, ReadFileErrorV2> {
+        let root = match &options.root {
+            Some(root) => Some(
+                fs.canonicalize(root)
+                    .map_err(|err| ReadFileErrorV2::from_io(root.clone(), err))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            fs,
+            options,
+            root,
+            loaded: HashSet::new(),
+            included_count: 0,
+            buffer: String::new(),
+            graph: IncludeGraph::default(),
+            origins: HashMap::new(),
+        })
+    }
+
+    fn is_loaded(&self, path: &Path) -> bool {
+        self.loaded.contains(path)
+    }
+
+    fn load_to_vec<D: Decimal>(
+        &mut self,
+        path: &Path,
+        source: Option<&str>,
+        is_included: bool,
+        prefetched: Option<Parsed<D>>,
+    ) -> Result<Vec<Entry<D>>, ReadFileErrorV2> {
+        let mut entries = Vec::new();
+        self.load(path, source, is_included, prefetched, |entry| {
+            entries.push(entry);
+        })?;
+        Ok(entries)
+    }
+
+    /// Read (unless the source is given or the file was parsed ahead of time) and parse the file,
+    /// invoking `on_entry` for each entry found
+    ///
+    /// The include entries contain the canonical path of each included file, and are recorded in the include graph.
+    /// The errors contain the chain of `include` directives that led to the file.
+    fn load<D: Decimal>(
+        &mut self,
+        path: &Path,
+        source: Option<&str>,
+        is_included: bool,
+        prefetched: Option<Parsed<D>>,
+        on_entry: impl FnMut(Entry<D>),
+    ) -> Result<(), ReadFileErrorV2> {
+        self.load_file(path, source, is_included, prefetched, on_entry)
+            .map_err(|mut err| {
+                let mut chain = self.include_chain(path);
+                chain.append(&mut err.include_chain);
+                err.include_chain = chain;
+                err
+            })
+    }
+
+    /// Returns the chain of `include` directives through which the file was first found
+    fn include_chain(&self, path: &Path) -> Vec<Include> {
+        let mut chain = Vec::new();
+        let mut path = path;
+        // There is no cycle, as an origin is only recorded for files that are not loaded yet
+        while let Some(include) = self.origins.get(path) {
+            chain.push(include.clone());
+            path = &include.from;
+        }
+        chain.reverse();
+        chain
+    }
+
+    fn load_file<D: Decimal>(
+        &mut self,
+        path: &Path,
+        source: Option<&str>,
+        is_included: bool,
+        prefetched: Option<Parsed<D>>,
+        mut on_entry: impl FnMut(Entry<D>),
+    ) -> Result<(), ReadFileErrorV2> {
+        if is_included {
+            self.included_count += 1;
+            if let Some(max) = self
+                .options
+                .limits
+                .max_included_files
+                .filter(|&max| self.included_count > max)
+            {
+                return Err(ReadFileErrorV2::from_limit(
+                    path.into(),
+                    LimitExceeded::IncludedFiles(max),
+                ));
+            }
+        }
+        self.loaded.insert(path.into());
+        if let Some(parsed) = prefetched {
+            for (entry, line_number) in parsed.entries {
+                self.resolve(path, entry, line_number, &mut on_entry)?;
+            }
+            return parsed.error.map_or(Ok(()), Err);
+        }
+        let (fs, limits) = (self.fs, &self.options.limits);
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let result = parse_file(
+            fs,
+            path,
+            source,
+            limits,
+            &mut buffer,
+            |entry, line_number| self.resolve(path, entry, line_number, &mut on_entry),
+        );
+        self.buffer = buffer;
+        result
+    }
+
+    /// Resolve the path of the include entries found in the file at `path`, then invoke `on_entry`
+    fn resolve<D>(
+        &mut self,
+        path: &Path,
+        entry: Entry<D>,
+        line_number: u32,
+        on_entry: &mut impl FnMut(Entry<D>),
+    ) -> Result<(), ReadFileErrorV2> {
+        let Entry::Include(include) = entry else {
+            on_entry(entry);
+            return Ok(());
+        };
+        let Some(parent) = path.parent() else {
+            unreachable!("there must be a parent if the file was valid")
+        };
+        // The include is checked lexically before touching the file system,
+        // so that the errors do not reveal which files exist outside of the root
+        if let Some(root) = &self.root {
+            let lexical = normalize(&parent.join(&include));
+            if !lexical_prefix(&lexical).starts_with(root) {
+                return Err(ReadFileErrorV2::from_outside_root(lexical, root.clone())
+                    .included_from(path, line_number));
+            }
+        }
+        let paths = resolve_include(self.fs, parent, &include)
+            .map_err(|err| err.included_from(path, line_number))?;
+        for included in paths {
+            let canonical = self.fs.canonicalize(&included).map_err(|err| {
+                ReadFileErrorV2::from_io(included.clone(), err).included_from(path, line_number)
+            })?;
+            // A symbolic link may still escape the root
+            if let Some(root) = self
+                .root
+                .as_ref()
+                .filter(|root| !canonical.starts_with(root))
+            {
+                return Err(ReadFileErrorV2::from_outside_root(included, root.clone())
+                    .included_from(path, line_number));
+            }
+            let included = canonical;
+            let include = Include {
+                from: path.into(),
+                line_number,
+                path: included.clone(),
+            };
+            if !self.is_loaded(&included) {
+                self.origins
+                    .entry(included.clone())
+                    .or_insert_with(|| include.clone());
+            }
+            self.graph.push(include);
+            on_entry(Entry::Include(included));
+        }
+        Ok(())
+    }
+}
+
+/// Read (unless the source is given) and parse the file, invoking `on_entry` for each entry found
+/// with the line number of its directive
+fn parse_file<D: Decimal>(
+    fs: &(impl FileSystem + ?Sized),
+    path: &Path,
+    source: Option<&str>,
+    limits: &Limits,
+    buffer: &mut String,
+    mut on_entry: impl FnMut(Entry<D>, u32) -> Result<(), ReadFileErrorV2>,
+) -> Result<(), ReadFileErrorV2> {
+    let source = if let Some(source) = source {
+        source
+    } else {
+        read(fs, path, limits, buffer)?;
+        buffer
+    };
+    check_limits(path, source, limits)?;
+    let file: Arc<Path> = path.into();
+    let max_expression_depth = limits
+        .max_expression_depth
+        .unwrap_or(DEFAULT_MAX_EXPRESSION_DEPTH);
+    let input = Span::new_extra(source, State::new(max_expression_depth));
+    let mut entries = Iter::new(source, nom::combinator::iterator(input, crate::entry::<D>));
+    while let Some(result) = entries.next() {
+        match result {
+            Ok(mut entry) => {
+                if let Entry::Directive(directive) = &mut entry {
+                    directive.file = Some(file.clone());
+                }
+                on_entry(entry, entries.include_line_number())?;
+            }
+            // Like for the other parse functions, the default depth is reported as a syntax error
+            Err(err)
+                if limits.max_expression_depth.is_some()
+                    && entries.error_kind() == Some(ErrorKind::TooLarge) =>
+            {
+                let limit = LimitExceeded::ExpressionDepth {
+                    max: max_expression_depth,
+                    line_number: err.line_number(),
+                };
+                return Err(ReadFileErrorV2::from_limit(path.into(), limit));
+            }
+            Err(err) => return Err(ReadFileErrorV2::from_syntax(path.into(), err)),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the paths designated by an `include` directive found in a file of the `parent` directory
+///
+/// The included path may be a glob pattern, in which case the matching paths are returned in alphabetical order.
+fn resolve_include(
+    fs: &(impl FileSystem + ?Sized),
+    parent: &Path,
+    include: &Path,
+) -> Result<Vec<PathBuf>, ReadFileErrorV2> {
+    let path = if include.is_relative() {
+        parent.join(include)
+    } else {
+        include.into()
+    };
+    let Some(pattern) = include
+        .to_str()
+        .filter(|include| include.contains(['*', '?', '[']))
+    else {
+        return Ok(vec![path]);
+    };
+    let pattern = if include.is_relative() {
+        let Some(parent) = parent.to_str() else {
+            return Ok(vec![path]);
+        };
+        let parent = glob::Pattern::escape(parent);
+        let separator = if parent.ends_with('/') { "" } else { "/" };
+        format!("{parent}{separator}{pattern}")
+    } else {
+        pattern.into()
+    };
+    let mut paths = fs
+        .glob(&pattern)
+        .map_err(|err| ReadFileErrorV2::from_io(path.clone(), err))?;
+    if paths.is_empty() {
+        return Err(ReadFileErrorV2::from_io(
+            path.clone(),
+            io::Error::new(io::ErrorKind::NotFound, "no file matches the pattern"),
+        ));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the leading components of the path that are not glob patterns
+fn lexical_prefix(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_str()
+                .map_or(false, |component| component.contains(['*', '?', '[']))
+        })
+        .collect()
+}
+
+/// Read the file content into the buffer, enforcing the file size limit
+fn read(
+    fs: &(impl FileSystem + ?Sized),
+    path: &Path,
+    limits: &Limits,
+    buffer: &mut String,
+) -> Result<(), ReadFileErrorV2> {
+    let mut file = fs
+        .open(path)
+        .map_err(|err| ReadFileErrorV2::from_io(path.into(), err))?;
+    let mut bytes = std::mem::take(buffer).into_bytes();
+    bytes.clear();
+    let result = match limits.max_file_size {
+        // Read one byte more than the limit, to detect files that are too large
+        // without having to trust the file metadata
+        Some(max) => file.take(max.saturating_add(1)).read_to_end(&mut bytes),
+        None => file.read_to_end(&mut bytes),
+    };
+    result.map_err(|err| ReadFileErrorV2::from_io(path.into(), err))?;
+    check_file_size(path, bytes.len(), limits)?;
+    *buffer = String::from_utf8(bytes).map_err(|err| {
+        ReadFileErrorV2::from_io(path.into(), io::Error::new(io::ErrorKind::InvalidData, err))
+    })?;
+    Ok(())
+}
+
+/// Enforce the file size and line length limits on the source of a file
+fn check_limits(path: &Path, source: &str, limits: &Limits) -> Result<(), ReadFileErrorV2> {
+    check_file_size(path, source.len(), limits)?;
+    if let Some(max) = limits.max_line_length {
+        if let Some(index) = source.lines().position(|line| line.len() > max) {
+            let limit = LimitExceeded::LineLength {
+                max,
+                line_number: u32::try_from(index + 1).unwrap_or(u32::MAX),
+            };
+            return Err(ReadFileErrorV2::from_limit(path.into(), limit));
+        }
+    }
+    Ok(())
+}
+
+fn check_file_size(path: &Path, size: usize, limits: &Limits) -> Result<(), ReadFileErrorV2> {
+    match limits.max_file_size {
+        Some(max) if size as u64 > max => Err(ReadFileErrorV2::from_limit(
+            path.into(),
+            LimitExceeded::FileSize(max),
+        )),
+        _ => Ok(()),
+    }
+}
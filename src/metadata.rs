@@ -65,7 +65,7 @@ impl Borrow<str> for Key {
 impl FromStr for Key {
     type Err = crate::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let span = Span::new(s);
+        let span = crate::span(s);
         match all_consuming(key).parse(span) {
             Ok((_, key)) => Ok(key),
             Err(_) => Err(crate::Error::new(s, span)),
@@ -0,0 +1,231 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+    ops::{Add, Div, Mul, Neg, Sub},
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::Decimal;
+
+/// A decimal number that remembers how it was written in the input
+///
+/// Use it as the decimal type (for example `BeancountFile<Number<f64>>`) when the written precision matters,
+/// like for inferring tolerances or formatting output the same way as the source.
+///
+/// Equality and ordering only consider the [`value`](Self::value).
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{BeancountFile, DirectiveContent, Number};
+/// let input = "2023-05-27 price CHF  4.10 PLN";
+/// let beancount: BeancountFile<Number<f64>> = input.parse().unwrap();
+/// let DirectiveContent::Price(price) = &beancount.directives[0].content else { unreachable!() };
+/// assert_eq!(*price.amount.value.value(), 4.1);
+/// assert_eq!(price.amount.value.precision(), Some(2));
+/// assert_eq!(price.amount.value.as_str(), Some("4.10"));
+/// assert_eq!(price.amount.value.to_string(), "4.10");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Number<D> {
+    value: D,
+    precision: Option<u32>,
+    text: Option<Arc<str>>,
+}
+
+impl<D> Number<D> {
+    /// Returns the decimal value
+    #[must_use]
+    pub fn value(&self) -> &D {
+        &self.value
+    }
+
+    /// Returns the decimal value
+    #[must_use]
+    pub fn into_value(self) -> D {
+        self.value
+    }
+
+    /// Number of fractional digits
+    ///
+    /// For a literal, it is the number of digits written after the decimal point (`0` for `10`, `2` for `10.00`).
+    /// For the result of an expression, it follows the usual decimal arithmetic rules: the largest precision
+    /// for additions and subtractions, and the sum of the precisions for multiplications.
+    ///
+    /// Returns `None` if the precision is not known, which is the case of divisions.
+    #[must_use]
+    pub fn precision(&self) -> Option<u32> {
+        self.precision
+    }
+
+    /// Returns the textual form of the number as found in the input, without thousands separators
+    ///
+    /// Returns `None` if the number is the result of an expression
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    fn computed(value: D, precision: Option<u32>) -> Self {
+        Self {
+            value,
+            precision,
+            text: None,
+        }
+    }
+}
+
+impl<D: FromStr> FromStr for Number<D> {
+    type Err = D::Err;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.parse()?;
+        let fractional_digits = s.split_once('.').map_or(0, |(_, fraction)| {
+            fraction.chars().take_while(char::is_ascii_digit).count()
+        });
+        Ok(Self {
+            value,
+            precision: Some(u32::try_from(fractional_digits).unwrap_or(u32::MAX)),
+            text: Some(Arc::from(s)),
+        })
+    }
+}
+
+impl<D: Default> Default for Number<D> {
+    fn default() -> Self {
+        Self::computed(D::default(), Some(0))
+    }
+}
+
+impl<D: Display> Display for Number<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.text {
+            Some(text) => Display::fmt(text, f),
+            None => Display::fmt(&self.value, f),
+        }
+    }
+}
+
+impl<D: PartialEq> PartialEq for Number<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<D: PartialOrd> PartialOrd for Number<D> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<D: Add<Output = D>> Add for Number<D> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        let precision = max_precision(self.precision, rhs.precision);
+        Self::computed(self.value + rhs.value, precision)
+    }
+}
+
+impl<D: Sub<Output = D>> Sub for Number<D> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        let precision = max_precision(self.precision, rhs.precision);
+        Self::computed(self.value - rhs.value, precision)
+    }
+}
+
+impl<D: Mul<Output = D>> Mul for Number<D> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let precision = product_precision(self.precision, rhs.precision);
+        Self::computed(self.value * rhs.value, precision)
+    }
+}
+
+impl<D: Div<Output = D>> Div for Number<D> {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self::Output {
+        Self::computed(self.value / rhs.value, None)
+    }
+}
+
+impl<D: Neg<Output = D>> Neg for Number<D> {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self::computed(-self.value, self.precision)
+    }
+}
+
+impl<D: Decimal> Decimal for Number<D> {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let precision = max_precision(self.precision, other.precision);
+        Some(Self::computed(
+            self.value.checked_add(other.value)?,
+            precision,
+        ))
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        let precision = max_precision(self.precision, other.precision);
+        Some(Self::computed(
+            self.value.checked_sub(other.value)?,
+            precision,
+        ))
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        let precision = product_precision(self.precision, other.precision);
+        Some(Self::computed(
+            self.value.checked_mul(other.value)?,
+            precision,
+        ))
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        Some(Self::computed(self.value.checked_div(other.value)?, None))
+    }
+}
+
+fn max_precision(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    a.zip(b).map(|(a, b)| a.max(b))
+}
+
+fn product_precision(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    a.zip(b).map(|(a, b)| a.saturating_add(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("10", Some(0))]
+    #[case("10.", Some(0))]
+    #[case("10.00", Some(2))]
+    #[case(".5", Some(1))]
+    #[case("-1.250", Some(3))]
+    fn from_str_should_record_precision(#[case] input: &str, #[case] expected: Option<u32>) {
+        let number: Number<f64> = input.parse().unwrap();
+        assert_eq!(number.precision(), expected);
+        assert_eq!(number.as_str(), Some(input));
+    }
+
+    #[rstest]
+    #[case("1.5 + 2.25", Some(2))]
+    #[case("1.5 - 2", Some(1))]
+    #[case("1.5 * 2.25", Some(3))]
+    #[case("1.5 / 2.25", None)]
+    fn expression_should_infer_precision(#[case] input: &str, #[case] expected: Option<u32>) {
+        let (_, number) = crate::amount::expression::<Number<f64>>(crate::span(input)).unwrap();
+        assert_eq!(number.precision(), expected);
+        assert_eq!(number.as_str(), None);
+    }
+
+    #[rstest]
+    fn equality_should_only_consider_value() {
+        let a: Number<f64> = "10".parse().unwrap();
+        let b: Number<f64> = "10.00".parse().unwrap();
+        assert_eq!(a, b);
+    }
+}
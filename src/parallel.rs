@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use crate::{
+    fs::FileSystem,
+    iterator::TagStack,
+    loader::{Parsed, Prefetch},
+    span, Decimal, Entry, Error, Limits, RawEntry,
+};
+
+/// Minimum size of the chunks of a single file parsed in parallel, as smaller chunks are not worth a thread
+const MIN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Parses the requested files on a pool of threads
+pub(crate) struct Prefetcher<D> {
+    jobs: mpsc::Sender<PathBuf>,
+    results: mpsc::Receiver<(PathBuf, thread::Result<Parsed<D>>)>,
+    ready: HashMap<PathBuf, thread::Result<Parsed<D>>>,
+    requested: HashSet<PathBuf>,
+    /// Number of files that may still be requested, so that no more than the included files limit are read
+    remaining: Option<usize>,
+}
+
+impl<D> Prefetch<D> for Prefetcher<D> {
+    fn request(&mut self, path: &Path) {
+        if self.remaining == Some(0) || self.requested.contains(path) {
+            return;
+        }
+        self.remaining = self.remaining.map(|remaining| remaining - 1);
+        self.requested.insert(path.into());
+        // The workers only stop once the prefetcher is dropped
+        let _ = self.jobs.send(path.into());
+    }
+
+    fn take(&mut self, path: &Path) -> Option<Parsed<D>> {
+        if !self.requested.contains(path) {
+            return None;
+        }
+        let result = loop {
+            if let Some(result) = self.ready.remove(path) {
+                break result;
+            }
+            let (path, result) = self.results.recv().ok()?;
+            self.ready.insert(path, result);
+        };
+        match result {
+            Ok(parsed) => Some(parsed),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Invoke `f` with a [`Prefetcher`] whose requests are parsed on one thread per available CPU
+pub(crate) fn with_prefetcher<D: Decimal + Send, R>(
+    fs: &(impl FileSystem + Sync + ?Sized),
+    limits: &Limits,
+    f: impl FnOnce(&mut Prefetcher<D>) -> R,
+) -> R {
+    let thread_count = thread_count();
+    let (jobs, job_receiver) = mpsc::channel::<PathBuf>();
+    let job_receiver = Mutex::new(job_receiver);
+    let (result_sender, results) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..thread_count {
+            let job_receiver = &job_receiver;
+            let result_sender = result_sender.clone();
+            scope.spawn(move || {
+                while let Some(path) = next_job(job_receiver) {
+                    let result =
+                        panic::catch_unwind(AssertUnwindSafe(|| Parsed::parse(fs, &path, limits)));
+                    if result_sender.send((path, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+        let mut prefetcher = Prefetcher {
+            jobs,
+            results,
+            ready: HashMap::new(),
+            requested: HashSet::new(),
+            remaining: limits.max_included_files,
+        };
+        let result = f(&mut prefetcher);
+        // Dropping the job sender stops the workers, so that the scope can end
+        drop(prefetcher);
+        result
+    })
+}
+
+fn next_job(job_receiver: &Mutex<mpsc::Receiver<PathBuf>>) -> Option<PathBuf> {
+    job_receiver.lock().ok()?.recv().ok()
+}
+
+fn thread_count() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Parse the input in chunks on one thread per available CPU
+///
+/// The result is the same as parsing the input sequentially.
+pub(crate) fn parse_in_chunks<D: Decimal + Send>(input: &str) -> Result<Vec<Entry<D>>, Error> {
+    let chunk_count = (input.len() / MIN_CHUNK_SIZE).clamp(1, thread_count());
+    parse_with_chunk_count(input, chunk_count)
+}
+
+fn parse_with_chunk_count<D: Decimal + Send>(
+    input: &str,
+    chunk_count: usize,
+) -> Result<Vec<Entry<D>>, Error> {
+    let boundaries = boundaries(input, chunk_count);
+    let mut chunks: Vec<Option<Segment<D>>> = thread::scope(|scope| {
+        let handles: Vec<_> = boundaries
+            .iter()
+            .enumerate()
+            .map(|(index, &start)| {
+                let end = boundaries
+                    .get(index + 1)
+                    .map_or(input.len(), |end| end.offset);
+                scope.spawn(move || parse_segment(input, start, end, |_| false))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| match handle.join() {
+                Ok(segment) => Some(segment),
+                Err(payload) => panic::resume_unwind(payload),
+            })
+            .collect()
+    });
+    let mut entries = Vec::new();
+    let mut index = 0;
+    while let Some(chunk) = chunks.get_mut(index).and_then(Option::take) {
+        if chunk.error.is_none() {
+            entries.extend(chunk.entries);
+            index += 1;
+            continue;
+        }
+        // Either the input is invalid, or the chunk does not end at the start of a directive
+        // (like when a multi-line string contains a line starting with a digit).
+        // Parse sequentially from the start of the chunk, until reaching the start of another chunk.
+        let next_chunks = &boundaries[index + 1..];
+        let segment = parse_segment(input, boundaries[index], input.len(), |offset| {
+            next_chunks
+                .binary_search_by_key(&offset, |boundary| boundary.offset)
+                .is_ok()
+        });
+        entries.extend(segment.entries);
+        if let Some(err) = segment.error {
+            return Err(err);
+        }
+        match boundaries.binary_search_by_key(&segment.end, |boundary| boundary.offset) {
+            Ok(next) => index = next,
+            Err(_) => break,
+        }
+    }
+    let mut tag_stack = TagStack::default();
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| tag_stack.apply(entry))
+        .collect())
+}
+
+/// Start of a chunk
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Boundary {
+    offset: usize,
+    line: u32,
+}
+
+/// Returns the start of the chunks, which are lines starting with a digit, as only directives start so
+fn boundaries(input: &str, chunk_count: usize) -> Vec<Boundary> {
+    let bytes = input.as_bytes();
+    let mut boundaries = vec![Boundary { offset: 0, line: 1 }];
+    for index in 1..chunk_count {
+        let previous = boundaries[boundaries.len() - 1];
+        let target = (input.len() * index / chunk_count).max(previous.offset);
+        let Some(offset) = next_line_starting_with_digit(bytes, target) else {
+            break;
+        };
+        let new_lines = input[previous.offset..offset].matches('\n').count();
+        boundaries.push(Boundary {
+            offset,
+            line: previous
+                .line
+                .saturating_add(u32::try_from(new_lines).unwrap_or(u32::MAX)),
+        });
+    }
+    boundaries
+}
+
+fn next_line_starting_with_digit(bytes: &[u8], from: usize) -> Option<usize> {
+    let mut offset = from;
+    loop {
+        offset += bytes[offset..].iter().position(|&byte| byte == b'\n')? + 1;
+        if bytes.get(offset).map_or(false, u8::is_ascii_digit) {
+            return Some(offset);
+        }
+    }
+}
+
+/// Raw entries parsed from a part of the input
+struct Segment<D> {
+    entries: Vec<RawEntry<D>>,
+    /// Offset at which the parsing stopped
+    end: usize,
+    /// Error found at the end offset
+    error: Option<Error>,
+}
+
+/// Parse the input from `start` up to the `end` offset, or until an entry ends at an offset for which `stop` returns true
+///
+/// The line numbers are those of the whole input.
+fn parse_segment<D: Decimal>(
+    source: &str,
+    start: Boundary,
+    end: usize,
+    mut stop: impl FnMut(usize) -> bool,
+) -> Segment<D> {
+    let shift = start.line - 1;
+    let mut input = span(&source[start.offset..end]);
+    let mut entries = Vec::new();
+    let error = loop {
+        if input.fragment().is_empty() || stop(start.offset + input.location_offset()) {
+            break None;
+        }
+        match crate::entry::<D>(input) {
+            Ok((rest, entry)) => {
+                entries.push(entry.shift_lines(shift));
+                input = rest;
+            }
+            Err(nom::Err::Error(_) | nom::Err::Incomplete(_)) => break Some(input),
+            Err(nom::Err::Failure(err)) => break Some(err.input),
+        }
+    };
+    Segment {
+        entries,
+        end: start.offset + input.location_offset(),
+        error: error.map(|at| {
+            Error::at(
+                source,
+                start.offset + at.location_offset(),
+                at.location_line() + shift,
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn sequential(input: &str) -> Result<Vec<Entry<f64>>, u32> {
+        crate::parse_iter::<f64>(input)
+            .collect::<Result<_, _>>()
+            .map_err(|err| err.line_number())
+    }
+
+    fn chunked(input: &str, chunk_count: usize) -> Result<Vec<Entry<f64>>, u32> {
+        parse_with_chunk_count::<f64>(input, chunk_count).map_err(|err| err.line_number())
+    }
+
+    #[rstest]
+    fn should_split_at_lines_starting_with_digit() {
+        let input = "; comment\n2024-01-01 open A\n  meta: 1\n2024-01-02 open B\n";
+        let boundaries = boundaries(input, 2);
+        let expected = [
+            Boundary { offset: 0, line: 1 },
+            Boundary {
+                offset: 38,
+                line: 4,
+            },
+        ];
+        assert_eq!(boundaries, expected);
+    }
+
+    #[rstest]
+    fn should_parse_official_example_like_sequential_parsing(
+        #[values(1, 2, 7, 100, 10_000)] chunk_count: usize,
+    ) {
+        let input = include_str!("../tests/samples/official.beancount");
+        assert_eq!(chunked(input, chunk_count), sequential(input));
+    }
+
+    #[rstest]
+    #[case::tags("pushtag #a\n2024-01-01 *\n2024-01-02 *\npoptag #a\n2024-01-03 *\n")]
+    #[case::multiline_string("2024-01-01 * \"a\n2024-01-02 open B\n\"\n2024-01-03 *\n")]
+    #[case::error_in_last_chunk("2024-01-01 *\n2024-01-02 *\n2024-01-03 oops\n")]
+    #[case::error_in_first_chunk("2024-01-01 oops\n2024-01-02 *\n2024-01-03 *\n")]
+    fn should_parse_like_sequential_parsing(#[case] input: &str) {
+        for chunk_count in 1..=4 {
+            assert_eq!(
+                chunked(input, chunk_count),
+                sequential(input),
+                "chunk count: {chunk_count}"
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use crate::{iterator::TagStack, span, Decimal, Entry, Error};
+
+/// Iterator over the entries parsed from a reader
+///
+/// Only the lines of the entries being parsed are kept in memory.
+pub(crate) struct ReaderIter<D, R> {
+    reader: R,
+    /// Lines read and not parsed yet, starting at the beginning of an entry
+    buffer: String,
+    /// Line number of the start of the buffer
+    line_number: u32,
+    /// Offset of the last line of the buffer that cannot be part of a preceding entry (unless in a string)
+    anchor: usize,
+    is_eof: bool,
+    is_done: bool,
+    entries: VecDeque<Entry<D>>,
+    /// Error to return once the entries found before it are returned
+    error: Option<Error>,
+    tag_stack: TagStack,
+}
+
+impl<D, R> ReaderIter<D, R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            line_number: 1,
+            anchor: 0,
+            is_eof: false,
+            is_done: false,
+            entries: VecDeque::new(),
+            error: None,
+            tag_stack: TagStack::default(),
+        }
+    }
+}
+
+impl<D: Decimal, R: BufRead> ReaderIter<D, R> {
+    /// Read lines until the buffer contains a new anchor, or the end of the input is reached
+    fn read_lines(&mut self) -> Result<(), Error> {
+        loop {
+            let start = self.buffer.len();
+            let read = self.reader.read_line(&mut self.buffer).map_err(|err| {
+                let lines = self.buffer.matches('\n').count();
+                Error::from_io(err, self.line_number + u32::try_from(lines).unwrap_or(0))
+            })?;
+            if read == 0 {
+                self.is_eof = true;
+                return Ok(());
+            }
+            if start > 0 && is_anchor(&self.buffer[start..]) {
+                self.anchor = start;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parse the entries of the buffer that end before the anchor, or all entries at the end of the input
+    ///
+    /// Returns an error if the input is invalid, after having added the entries found before the error
+    fn parse_buffer(&mut self) -> Result<(), Error> {
+        let limit = if self.is_eof {
+            self.buffer.len()
+        } else {
+            self.anchor
+        };
+        let shift = self.line_number - 1;
+        let mut input = span(&self.buffer);
+        let mut raw_entries = Vec::new();
+        let mut error = None;
+        while input.location_offset() < limit {
+            let at = match crate::entry::<D>(input) {
+                Ok((rest, entry)) if self.is_eof || rest.location_offset() <= limit => {
+                    raw_entries.push(entry.shift_lines(shift));
+                    input = rest;
+                    continue;
+                }
+                // The entry may continue after the anchor, like when a string contains a line break
+                Ok(_) => break,
+                Err(nom::Err::Error(_) | nom::Err::Incomplete(_)) => input,
+                Err(nom::Err::Failure(err)) => err.input,
+            };
+            // The entry may be invalid only because the end of a string is not read yet
+            if !self.is_eof && ends_in_string(&input.fragment()[..limit - input.location_offset()])
+            {
+                break;
+            }
+            error = Some(Error::at(
+                self.buffer.as_str(),
+                at.location_offset(),
+                at.location_line() + shift,
+            ));
+            break;
+        }
+        let consumed = input.location_offset();
+        let lines = self.buffer[..consumed].matches('\n').count();
+        self.line_number += u32::try_from(lines).unwrap_or(u32::MAX);
+        self.buffer.drain(..consumed);
+        self.anchor = self.anchor.saturating_sub(consumed);
+        self.entries.extend(
+            raw_entries
+                .into_iter()
+                .filter_map(|entry| self.tag_stack.apply(entry)),
+        );
+        error.map_or(Ok(()), Err)
+    }
+}
+
+impl<D: Decimal, R: BufRead> Iterator for ReaderIter<D, R> {
+    type Item = Result<Entry<D>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Some(Ok(entry));
+            }
+            if self.is_done {
+                return self.error.take().map(Err);
+            }
+            if self.is_eof && self.buffer.is_empty() {
+                self.is_done = true;
+                return None;
+            }
+            if let Err(err) = self.read_lines().and_then(|()| self.parse_buffer()) {
+                self.is_done = true;
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Returns true if the line cannot be part of a preceding entry, which is the case of lines starting
+/// with something else than a space or a comment
+pub(crate) fn is_anchor(line: &str) -> bool {
+    !line.starts_with([' ', '\t', ';', '\r', '\n'])
+}
+
+/// Returns true if the text ends within a string, ignoring the comments
+fn ends_in_string(text: &str) -> bool {
+    let mut is_in_string = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => is_in_string = !is_in_string,
+            '\\' if is_in_string => {
+                chars.next();
+            }
+            ';' if !is_in_string => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            _ => (),
+        }
+    }
+    is_in_string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("2024-01-01 open A", false)]
+    #[case("2024-01-01 * \"a", true)]
+    #[case("2024-01-01 * \"a\\\"", true)]
+    #[case("2024-01-01 * \"a\\\\\"", false)]
+    #[case("2024-01-01 * \"a\" ; \"b", false)]
+    #[case("2024-01-01 * \"a;\n\"", false)]
+    fn should_detect_unterminated_string(#[case] text: &str, #[case] expected: bool) {
+        assert_eq!(ends_in_string(text), expected);
+    }
+}
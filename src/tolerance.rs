@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    amount::{abs, max, min},
+    Amount, BeanOption, Currency, Decimal, InterpolationError, Number, PostingPrice, Transaction,
+};
+
+/// Tolerances allowed for the residual of the transactions
+///
+/// They are configured by the `inferred_tolerance_default`, `inferred_tolerance_multiplier`
+/// and `infer_tolerance_from_cost` options.
+///
+/// See: <https://beancount.github.io/docs/precision_tolerances.html>
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{BeancountFile, Tolerances};
+/// let input = r#"
+/// option "inferred_tolerance_default" "CHF:0.01"
+/// option "inferred_tolerance_multiplier" "0.6"
+/// "#;
+/// let beancount: BeancountFile<f64> = input.parse().unwrap();
+/// let tolerances = Tolerances::<f64>::from_options(&beancount.options);
+/// assert_eq!(tolerances.default("CHF"), Some(&0.01));
+/// assert_eq!(tolerances.default("EUR"), None);
+/// assert_eq!(tolerances.multiplier(), Some(&0.6));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tolerances<D> {
+    defaults: HashMap<String, D>,
+    multiplier: Option<D>,
+    from_cost: bool,
+}
+
+impl<D: Decimal> Tolerances<D> {
+    /// Tolerances configured by the given options
+    ///
+    /// The options that are not related to tolerances are ignored, as well as the ones with an invalid value.
+    #[must_use]
+    pub fn from_options(options: &[BeanOption]) -> Self {
+        let mut tolerances = Self {
+            defaults: HashMap::new(),
+            multiplier: "0.5".parse().ok(),
+            from_cost: false,
+        };
+        for option in options {
+            match option.name.as_str() {
+                "inferred_tolerance_default" => {
+                    if let Some((currency, value)) = option.value.split_once(':') {
+                        if let Ok(value) = value.parse() {
+                            tolerances.defaults.insert(currency.into(), value);
+                        }
+                    }
+                }
+                "inferred_tolerance_multiplier" => {
+                    if let Ok(multiplier) = option.value.parse() {
+                        tolerances.multiplier = Some(multiplier);
+                    }
+                }
+                "infer_tolerance_from_cost" => {
+                    tolerances.from_cost = option.value.eq_ignore_ascii_case("true");
+                }
+                _ => (),
+            }
+        }
+        tolerances
+    }
+
+    /// Returns the default tolerance of the currency (`inferred_tolerance_default` option)
+    ///
+    /// The default tolerance of all currencies (`*`) is returned if there is none for the given currency.
+    #[must_use]
+    pub fn default(&self, currency: &str) -> Option<&D> {
+        self.defaults
+            .get(currency)
+            .or_else(|| self.defaults.get("*"))
+    }
+
+    /// Returns the multiplier of the tolerances inferred from the precision of the numbers
+    /// (`inferred_tolerance_multiplier` option, `0.5` by default)
+    #[must_use]
+    pub fn multiplier(&self) -> Option<&D> {
+        self.multiplier.as_ref()
+    }
+
+    /// Returns true if the costs and prices are used to infer tolerances (`infer_tolerance_from_cost` option)
+    #[must_use]
+    pub fn from_cost(&self) -> bool {
+        self.from_cost
+    }
+
+    /// Returns the tolerance inferred from a number written with the given number of fractional digits
+    ///
+    /// There is no tolerance inferred from integers, nor if the tolerance overflows.
+    pub(crate) fn inferred(&self, precision: u32) -> Option<D> {
+        if precision == 0 {
+            return None;
+        }
+        let unit: D = format!("0.{}1", "0".repeat(precision as usize - 1))
+            .parse()
+            .ok()?;
+        unit.checked_mul(self.multiplier.clone()?)
+    }
+}
+
+/// Error returned by [`Transaction::check_balance`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TransactionBalanceError<D> {
+    /// The weights of the postings do not sum up to zero
+    ///
+    /// Contains the residual of the currencies exceeding their tolerance.
+    Unbalanced(Vec<Residual<D>>),
+    /// The residual of the transaction cannot be computed
+    Interpolation(InterpolationError),
+}
+
+/// Residual of a currency exceeding its tolerance, see [`TransactionBalanceError::Unbalanced`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Residual<D> {
+    /// Sum of the weights of the postings in the currency
+    pub amount: Amount<D>,
+    /// Tolerance of the currency
+    pub tolerance: D,
+}
+
+impl<D: Display> Display for TransactionBalanceError<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionBalanceError::Unbalanced(residuals) => {
+                write!(f, "Transaction does not balance:")?;
+                for (index, residual) in residuals.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "," };
+                    write!(
+                        f,
+                        "{separator} {} {}",
+                        residual.amount.value, residual.amount.currency
+                    )?;
+                }
+                Ok(())
+            }
+            TransactionBalanceError::Interpolation(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl<D: std::fmt::Debug + Display> std::error::Error for TransactionBalanceError<D> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransactionBalanceError::Unbalanced(_) => None,
+            TransactionBalanceError::Interpolation(err) => Some(err),
+        }
+    }
+}
+
+impl<D: Decimal + PartialOrd + Default> Transaction<Number<D>> {
+    /// Verify that the weights of the postings sum up to zero in each currency, within the tolerances
+    ///
+    /// Like beancount, the tolerance of a currency is the largest of:
+    /// * the default tolerance of the currency, see [`Tolerances::default`]
+    /// * the tolerance inferred from the least precise number of the currency among the posting amounts,
+    ///   which is the [multiplier](Tolerances::multiplier) times the value of its last digit
+    ///   (`0.005` for `10.00 CHF`, and none for `10 CHF`)
+    /// * if [`Tolerances::from_cost`] is enabled, the sum of the tolerances inferred from the costs and prices
+    ///   in that currency, each being the tolerance of the amount multiplied by the unit cost or price
+    ///   (with a maximum of `0.5`)
+    ///
+    /// The tolerances that cannot be computed because of an overflow are ignored.
+    ///
+    /// If a posting has no amount, it receives the residual (see [`Transaction::interpolate`]),
+    /// so that the transaction balances.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use beancount_parser::{BeancountFile, DirectiveContent, Number, Tolerances, TransactionBalanceError};
+    /// let input = r#"
+    /// 2024-01-03 * "Shop"
+    ///   Assets:Cash      -10.004 CHF
+    ///   Expenses:Food     10.00 CHF
+    /// 2024-01-03 * "Shop"
+    ///   Assets:Cash      -10.01 CHF
+    ///   Expenses:Food     10.00 CHF
+    /// "#;
+    /// let beancount: BeancountFile<Number<f64>> = input.parse().unwrap();
+    /// let tolerances = Tolerances::from_options(&beancount.options);
+    /// let DirectiveContent::Transaction(trx) = &beancount.directives[0].content else { unreachable!() };
+    /// assert!(trx.check_balance(&tolerances).is_ok());
+    /// let DirectiveContent::Transaction(trx) = &beancount.directives[1].content else { unreachable!() };
+    /// let Err(TransactionBalanceError::Unbalanced(residuals)) = trx.check_balance(&tolerances) else {
+    ///     panic!("should not balance")
+    /// };
+    /// assert_eq!(residuals[0].amount.currency.as_str(), "CHF");
+    /// assert!((residuals[0].amount.value + 0.01).abs() < 1e-9);
+    /// assert_eq!(residuals[0].tolerance, 0.005);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransactionBalanceError::Unbalanced`] with the residuals exceeding their tolerance,
+    /// and [`TransactionBalanceError::Interpolation`] if the residual cannot be computed
+    /// (more than one posting without amount, or a cost without amount).
+    pub fn check_balance(
+        &self,
+        tolerances: &Tolerances<D>,
+    ) -> Result<(), TransactionBalanceError<D>> {
+        let residual = self
+            .residual()
+            .map_err(TransactionBalanceError::Interpolation)?;
+        let missing: Vec<usize> = self
+            .postings
+            .iter()
+            .enumerate()
+            .filter(|(_, posting)| posting.amount.is_none())
+            .map(|(index, _)| index)
+            .collect();
+        match missing.len() {
+            0 => (),
+            1 => return Ok(()),
+            _ => {
+                return Err(TransactionBalanceError::Interpolation(
+                    InterpolationError::AmbiguousPostings(missing),
+                ))
+            }
+        }
+        let inferred = self.inferred_tolerances(tolerances);
+        let zero = D::default();
+        let residuals: Vec<Residual<D>> = residual
+            .into_iter()
+            .filter_map(|amount| {
+                let value = amount.value.into_value();
+                let tolerance = [
+                    tolerances.default(amount.currency.as_str()).cloned(),
+                    inferred.get(&amount.currency).cloned(),
+                ]
+                .into_iter()
+                .flatten()
+                .fold(zero.clone(), max);
+                (abs(value.clone()) > tolerance).then(|| Residual {
+                    amount: Amount {
+                        value,
+                        currency: amount.currency,
+                    },
+                    tolerance,
+                })
+            })
+            .collect();
+        if residuals.is_empty() {
+            Ok(())
+        } else {
+            Err(TransactionBalanceError::Unbalanced(residuals))
+        }
+    }
+
+    /// Returns the tolerances inferred from the numbers of the postings, per currency
+    fn inferred_tolerances(&self, tolerances: &Tolerances<D>) -> HashMap<Currency, D> {
+        let maximum: Option<D> = "0.5".parse().ok();
+        let mut inferred: HashMap<Currency, D> = HashMap::new();
+        let mut from_cost: HashMap<Currency, D> = HashMap::new();
+        for posting in &self.postings {
+            let Some(amount) = &posting.amount else {
+                continue;
+            };
+            let Some(tolerance) = amount
+                .value
+                .precision()
+                .and_then(|precision| tolerances.inferred(precision))
+            else {
+                continue;
+            };
+            merge(
+                &mut inferred,
+                &amount.currency,
+                tolerance.clone(),
+                |a, b| Some(max(a, b)),
+            );
+            if !tolerances.from_cost {
+                continue;
+            }
+            let cost = posting
+                .cost
+                .as_ref()
+                .and_then(|cost| cost.amount.as_ref())
+                .map(|cost| (cost.value.value().clone(), &cost.currency));
+            let price = posting.price.as_ref().and_then(|price| match price {
+                PostingPrice::Unit(price) => Some((price.value.value().clone(), &price.currency)),
+                PostingPrice::Total(price) => {
+                    let units = abs(amount.value.value().clone());
+                    (units > D::default())
+                        .then(|| price.value.value().clone().checked_div(units))
+                        .flatten()
+                        .map(|unit_price| (unit_price, &price.currency))
+                }
+            });
+            for (unit_value, currency) in cost.into_iter().chain(price) {
+                // The tolerances that overflow are ignored
+                let Some(mut tolerance) = tolerance.clone().checked_mul(unit_value) else {
+                    continue;
+                };
+                if let Some(maximum) = &maximum {
+                    tolerance = min(tolerance, maximum.clone());
+                }
+                merge(&mut from_cost, currency, tolerance, D::checked_add);
+            }
+        }
+        for (currency, tolerance) in from_cost {
+            merge(&mut inferred, &currency, tolerance, |a, b| Some(max(a, b)));
+        }
+        inferred
+    }
+}
+
+/// Merge the value into the value of the currency, which is left unchanged if the merge function returns `None`
+fn merge<D: Clone>(
+    map: &mut HashMap<Currency, D>,
+    currency: &Currency,
+    value: D,
+    f: fn(D, D) -> Option<D>,
+) {
+    match map.get_mut(currency) {
+        Some(existing) => {
+            if let Some(merged) = f(existing.clone(), value) {
+                *existing = merged;
+            }
+        }
+        None => {
+            map.insert(currency.clone(), value);
+        }
+    }
+}
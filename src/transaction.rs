@@ -8,8 +8,9 @@ use nom::{
     bytes::complete::{tag, take_while},
     character::complete::satisfy,
     character::complete::{char as char_tag, space0, space1},
-    combinator::{cut, iterator, map, opt, success, value},
-    sequence::{delimited, preceded, separated_pair, terminated},
+    combinator::{cut, iterator, map, map_opt, opt, value},
+    multi::separated_list0,
+    sequence::{delimited, preceded, terminated},
     Parser,
 };
 
@@ -118,6 +119,94 @@ impl<D> Posting<D> {
     }
 }
 
+impl<D: Decimal + PartialOrd + Default> Posting<D> {
+    /// Returns the weight of the posting, which is the amount used to check that a transaction balances
+    ///
+    /// Like in beancount, the weight is:
+    /// * the amount multiplied by the cost, if there is a cost (`{...}`)
+    /// * otherwise, the amount multiplied by the unit price (`@`), or the total price (`@@`) with the sign of the amount
+    /// * otherwise, the amount itself
+    ///
+    /// # Example
+    /// ```
+    /// # use beancount_parser::{BeancountFile, DirectiveContent};
+    /// let input = r#"
+    /// 2024-01-03 * "Exchange"
+    ///   Assets:Cash:EUR     10 EUR @ 1.5 CHF
+    ///   Assets:Cash:CHF    -15 CHF
+    /// "#;
+    /// let beancount: BeancountFile<f64> = input.parse().unwrap();
+    /// let DirectiveContent::Transaction(trx) = &beancount.directives[0].content else { unreachable!() };
+    /// let weight = trx.postings[0].weight().unwrap();
+    /// assert_eq!(weight.value, 15.0);
+    /// assert_eq!(weight.currency.as_str(), "CHF");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`WeightError`] if the posting has no amount, or a cost without amount (like `{}`),
+    /// or if the amount multiplied by the cost or price overflows
+    pub fn weight(&self) -> Result<Amount<D>, WeightError> {
+        let amount = self.amount.as_ref().ok_or(WeightError::MissingAmount)?;
+        if let Some(cost) = &self.cost {
+            let cost = cost.amount.as_ref().ok_or(WeightError::MissingCost)?;
+            return Ok(Amount {
+                value: amount
+                    .value
+                    .clone()
+                    .checked_mul(cost.value.clone())
+                    .ok_or(WeightError::Overflow)?,
+                currency: cost.currency.clone(),
+            });
+        }
+        Ok(match &self.price {
+            Some(PostingPrice::Unit(price)) => Amount {
+                value: amount
+                    .value
+                    .clone()
+                    .checked_mul(price.value.clone())
+                    .ok_or(WeightError::Overflow)?,
+                currency: price.currency.clone(),
+            },
+            Some(PostingPrice::Total(price)) => Amount {
+                value: if amount.value < D::default() {
+                    -price.value.clone()
+                } else {
+                    price.value.clone()
+                },
+                currency: price.currency.clone(),
+            },
+            None => amount.clone(),
+        })
+    }
+}
+
+/// Error returned by [`Posting::weight`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum WeightError {
+    /// The posting has no amount
+    MissingAmount,
+    /// The posting has a cost without amount (like `{}`)
+    ///
+    /// Its weight depends on the lots it is booked against.
+    MissingCost,
+    /// The amount multiplied by the cost or price overflows (see [`Decimal::checked_mul`])
+    Overflow,
+}
+
+impl Display for WeightError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightError::MissingAmount => write!(f, "The posting has no amount"),
+            WeightError::MissingCost => write!(f, "The posting has a cost without amount"),
+            WeightError::Overflow => write!(f, "The weight of the posting overflows"),
+        }
+    }
+}
+
+impl std::error::Error for WeightError {}
+
 /// Cost of a posting
 ///
 /// It is the amount within `{` and `}`.
@@ -128,6 +217,8 @@ pub struct Cost<D> {
     pub amount: Option<Amount<D>>,
     /// The date of this cost basis
     pub date: Option<Date>,
+    /// Label of the lot (like `"first-lot"` in `{100 USD, "first-lot"}`)
+    pub label: Option<String>,
 }
 
 /// Price of a posting
@@ -384,28 +475,47 @@ fn posting<D: Decimal>(input: Span<'_>) -> IResult<'_, Posting<D>> {
 
 fn cost<D: Decimal>(input: Span<'_>) -> IResult<'_, Cost<D>> {
     let (input, _) = terminated(char_tag('{'), space0).parse(input)?;
-    let (input, (cost, date)) = alt((
-        map(
-            separated_pair(
-                amount::parse,
-                delimited(space0, char_tag(','), space0),
-                date::parse,
-            ),
-            |(a, d)| (Some(a), Some(d)),
-        ),
-        map(
-            separated_pair(
-                date::parse,
-                delimited(space0, char_tag(','), space0),
-                amount::parse,
-            ),
-            |(d, a)| (Some(a), Some(d)),
-        ),
-        map(amount::parse, |a| (Some(a), None)),
-        map(date::parse, |d| (None, Some(d))),
-        map(success(true), |_| (None, None)),
-    ))
+    let (input, cost) = map_opt(
+        separated_list0(delimited(space0, char_tag(','), space0), cost_component),
+        |components| {
+            components.into_iter().try_fold(
+                Cost {
+                    amount: None,
+                    date: None,
+                    label: None,
+                },
+                |mut cost, component| {
+                    match component {
+                        CostComponent::Amount(amount) if cost.amount.is_none() => {
+                            cost.amount = Some(amount);
+                        }
+                        CostComponent::Date(date) if cost.date.is_none() => cost.date = Some(date),
+                        CostComponent::Label(label) if cost.label.is_none() => {
+                            cost.label = Some(label);
+                        }
+                        _ => return None,
+                    }
+                    Some(cost)
+                },
+            )
+        },
+    )
     .parse(input)?;
     let (input, _) = preceded(space0, char_tag('}')).parse(input)?;
-    Ok((input, Cost { amount: cost, date }))
+    Ok((input, cost))
+}
+
+enum CostComponent<D> {
+    Amount(Amount<D>),
+    Date(Date),
+    Label(String),
+}
+
+fn cost_component<D: Decimal>(input: Span<'_>) -> IResult<'_, CostComponent<D>> {
+    alt((
+        map(amount::parse, CostComponent::Amount),
+        map(date::parse, CostComponent::Date),
+        map(string, CostComponent::Label),
+    ))
+    .parse(input)
 }
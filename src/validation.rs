@@ -0,0 +1,613 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    amount::abs, Account, Amount, Balance, BeanOption, BookingEngine, Currency, Date, Decimal,
+    Directive, DirectiveContent, Inventory, Number, Position, Posting, Tolerances, Transaction,
+};
+
+/// Verify the balance assertions of the directives
+///
+/// The directives are walked in the order of beancount: by date, and for each date the `open` directives first,
+/// then the `balance` directives, then the other directives, and the `close` directives last.
+/// So that a balance assertion verifies the balance at the beginning of its date.
+///
+/// The transactions are booked (see [`BookingEngine`]) to interpolate their posting without amount,
+/// and the units of each account, including its sub-accounts, are compared to the amount of the balance assertion.
+///
+/// The tolerance of a balance assertion is its explicit tolerance (written after `~`) if any.
+/// Otherwise, like beancount, it is twice the tolerance inferred from the precision of the amount
+/// (see [`Tolerances::multiplier`]), so `0.01` for `10.00 CHF`, and zero for an integer amount.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{check_balances, BeancountFile, Number};
+/// let input = r#"
+/// 2024-01-01 open Assets:Bank:Checking
+/// 2024-01-01 open Assets:Bank:Savings
+/// 2024-01-01 open Equity:Opening
+/// 2024-01-02 * "Opening"
+///   Assets:Bank:Checking   100.00 CHF
+///   Assets:Bank:Savings    200.00 CHF
+///   Equity:Opening
+/// 2024-01-02 balance Assets:Bank 0 CHF
+/// 2024-01-03 balance Assets:Bank 300.00 CHF
+/// 2024-01-03 balance Assets:Bank:Checking 100.1 CHF ~ 0.2
+/// 2024-01-03 balance Assets:Bank:Savings 210 CHF
+/// "#;
+/// let beancount: BeancountFile<Number<f64>> = input.parse().unwrap();
+/// let errors = check_balances(&beancount.directives, &beancount.options);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].directive.line_number, 12);
+/// assert_eq!(errors[0].actual.as_ref().unwrap().value, 200.0);
+/// assert_eq!(errors[0].difference, Some(-10.0));
+/// assert_eq!(
+///     errors[0].to_string(),
+///     "Balance failed for Assets:Bank:Savings: expected 210 CHF, but was 200 CHF (difference: -10 CHF)"
+/// );
+/// ```
+#[must_use]
+pub fn check_balances<'a, D: Decimal + PartialOrd + Default>(
+    directives: &'a [Directive<Number<D>>],
+    options: &[BeanOption],
+) -> Vec<BalanceError<'a, D>> {
+    let tolerances = Tolerances::<D>::from_options(options);
+    let mut ledger = Ledger::<D>::new(options);
+    let mut errors = Vec::new();
+    for directive in sorted(directives) {
+        if let DirectiveContent::Balance(balance) = &directive.content {
+            let expected = Amount {
+                value: balance.amount.value.value().clone(),
+                currency: balance.amount.currency.clone(),
+            };
+            let actual = ledger.units(&balance.account, expected.currency.as_str());
+            let difference = actual
+                .clone()
+                .and_then(|actual| actual.checked_sub(expected.value.clone()));
+            let tolerance = balance_tolerance(balance, &tolerances);
+            if difference
+                .clone()
+                .map_or(true, |difference| abs(difference) > tolerance)
+            {
+                errors.push(BalanceError {
+                    directive,
+                    actual: actual.map(|value| Amount {
+                        value,
+                        currency: expected.currency.clone(),
+                    }),
+                    expected,
+                    difference,
+                    tolerance,
+                });
+            }
+        } else {
+            ledger.apply(directive);
+        }
+    }
+    errors
+}
+
+/// Expand the `pad` directives into the transactions padding their account
+///
+/// Like beancount, a `pad` directive pads its account for the first `balance` directive of each currency
+/// following it (until the next `pad` directive of the same account). If the units held by the account
+/// (not including its sub-accounts) differ from the amount of the balance assertion by more than its tolerance,
+/// a transaction flagged `P` moves the difference from the source account of the `pad` directive.
+///
+/// Returns the directives, with the padding transactions inserted after their `pad` directive,
+/// and the errors for the `pad` directives that did not pad any balance assertion.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{check_balances, expand_pads, BeancountFile, DirectiveContent, Number};
+/// let input = r#"
+/// 2024-01-01 open Assets:Cash
+/// 2024-01-01 open Equity:Opening
+/// 2024-01-01 pad Assets:Cash Equity:Opening
+/// 2024-01-02 balance Assets:Cash 100.00 CHF
+/// 2024-01-02 pad Assets:Cash Equity:Opening
+/// 2024-01-03 balance Assets:Cash 100.00 CHF
+/// "#;
+/// let beancount: BeancountFile<Number<f64>> = input.parse().unwrap();
+/// let (directives, errors) = expand_pads(&beancount.directives, &beancount.options);
+/// assert_eq!(directives.len(), 7);
+/// let DirectiveContent::Transaction(padding) = &directives[3].content else { unreachable!() };
+/// assert_eq!(padding.flag, Some('P'));
+/// assert_eq!(padding.postings[0].account.as_str(), "Assets:Cash");
+/// assert_eq!(*padding.postings[0].amount.as_ref().unwrap().value.value(), 100.0);
+/// assert_eq!(padding.postings[1].account.as_str(), "Equity:Opening");
+/// assert!(check_balances(&directives, &beancount.options).is_empty());
+///
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].directive.line_number, 6);
+/// assert_eq!(errors[0].to_string(), "Unused pad directive");
+/// ```
+#[must_use]
+pub fn expand_pads<'a, D: Decimal + PartialOrd + Default + Display>(
+    directives: &'a [Directive<Number<D>>],
+    options: &[BeanOption],
+) -> (Vec<Directive<Number<D>>>, Vec<PadError<'a, D>>) {
+    let tolerances = Tolerances::<D>::from_options(options);
+    let mut ledger = Ledger::<D>::new(options);
+    // Index of the active pad directive of the accounts, with the currencies already padded
+    let mut active: HashMap<&Account, (usize, HashSet<&Currency>)> = HashMap::new();
+    let mut paddings: HashMap<usize, Vec<Directive<Number<D>>>> = HashMap::new();
+    let order = sorted_indices(directives);
+    for &index in &order {
+        let directive = &directives[index];
+        match &directive.content {
+            DirectiveContent::Pad(pad) => {
+                active.insert(&pad.account, (index, HashSet::new()));
+            }
+            DirectiveContent::Balance(balance) => {
+                let currency = &balance.amount.currency;
+                let Some((pad_index, padded)) = active.get_mut(&balance.account) else {
+                    continue;
+                };
+                if padded.contains(currency) {
+                    continue;
+                }
+                // The account is not padded if its units, or their difference with the balance, overflow
+                let Some(difference) = ledger
+                    .account_units(&balance.account, currency.as_str())
+                    .and_then(|actual| balance.amount.value.clone().checked_sub(actual))
+                else {
+                    continue;
+                };
+                if abs(difference.value().clone()) <= balance_tolerance(balance, &tolerances) {
+                    continue;
+                }
+                padded.insert(currency);
+                let padding = padding(&directives[*pad_index], balance, difference);
+                ledger.apply(&padding);
+                paddings.entry(*pad_index).or_default().push(padding);
+            }
+            _ => ledger.apply(directive),
+        }
+    }
+    let errors = order
+        .into_iter()
+        .filter(|index| {
+            matches!(directives[*index].content, DirectiveContent::Pad(_))
+                && !paddings.contains_key(index)
+        })
+        .map(|index| PadError {
+            directive: &directives[index],
+        })
+        .collect();
+    let mut expanded = Vec::with_capacity(directives.len() + paddings.len());
+    for (index, directive) in directives.iter().enumerate() {
+        expanded.push(directive.clone());
+        if let Some(padding) = paddings.remove(&index) {
+            expanded.extend(padding);
+        }
+    }
+    (expanded, errors)
+}
+
+/// Verify that the directives reference accounts that are open at their date
+///
+/// The directives are walked in the order of beancount (see [`check_balances`]), so that an account may be
+/// referenced at the date of its `open` directive, and until the date of its `close` directive included.
+///
+/// The following problems are reported:
+/// * A posting, `balance` or `pad` directive referencing an account that is not open, or already closed
+/// * An `open` directive for an account that was already opened
+/// * A `close` directive for an account that is not open, or that still holds units
+///
+/// The units held by the accounts are only computed from the transactions, so the padding transactions
+/// must be inserted first (see [`expand_pads`]) for the `close` directives of padded accounts to be verified.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{check_accounts, AccountErrorKind, BeancountFile, Number};
+/// let input = r#"
+/// 2024-01-01 open Assets:Cash
+/// 2024-01-01 open Expenses:Food
+/// 2024-01-02 * "Shop"
+///   Assets:Cash    -10.00 CHF
+///   Expenses:Food
+/// 2024-01-02 close Assets:Cash
+/// 2024-01-03 * "Shop"
+///   Assets:Cash    -10.00 CHF
+///   Expenses:Other
+/// "#;
+/// let beancount: BeancountFile<Number<f64>> = input.parse().unwrap();
+/// let errors = check_accounts(&beancount.directives, &beancount.options);
+/// assert_eq!(errors.len(), 3);
+/// assert_eq!(errors[0].to_string(), "Account Assets:Cash is not empty");
+/// assert_eq!(errors[1].kind, AccountErrorKind::Closed(beancount.directives[3].date));
+/// assert_eq!(errors[1].to_string(), "Account Assets:Cash is closed since 2024-01-02");
+/// assert_eq!(errors[2].to_string(), "Account Expenses:Other is not open");
+/// ```
+#[must_use]
+pub fn check_accounts<'a, D: Decimal + PartialOrd + Default>(
+    directives: &'a [Directive<Number<D>>],
+    options: &[BeanOption],
+) -> Vec<AccountError<'a, D>> {
+    let mut ledger = Ledger::<D>::new(options);
+    let mut accounts = Accounts::default();
+    for directive in sorted(directives) {
+        match &directive.content {
+            DirectiveContent::Open(open) => {
+                if let Some((opened, _)) = accounts.dates.get(&open.account) {
+                    let kind = AccountErrorKind::AlreadyOpen(*opened);
+                    accounts.report(directive, &open.account, kind);
+                } else {
+                    accounts
+                        .dates
+                        .insert(open.account.clone(), (directive.date, None));
+                }
+            }
+            DirectiveContent::Close(close) if accounts.check(directive, &close.account) => {
+                if ledger
+                    .inventories
+                    .get(&close.account)
+                    .map_or(false, |inventory| !inventory.is_empty())
+                {
+                    accounts.report(directive, &close.account, AccountErrorKind::NotEmpty);
+                }
+                if let Some((_, closed)) = accounts.dates.get_mut(&close.account) {
+                    *closed = Some(directive.date);
+                }
+            }
+            DirectiveContent::Transaction(transaction) => {
+                for posting in &transaction.postings {
+                    accounts.check(directive, &posting.account);
+                }
+            }
+            DirectiveContent::Balance(balance) => {
+                accounts.check(directive, &balance.account);
+            }
+            DirectiveContent::Pad(pad) => {
+                accounts.check(directive, &pad.account);
+                accounts.check(directive, &pad.source_account);
+            }
+            _ => (),
+        }
+        ledger.apply(directive);
+    }
+    accounts.errors
+}
+
+/// Open date and close date of the accounts, with the errors found so far
+struct Accounts<'a, D> {
+    dates: HashMap<Account, (Date, Option<Date>)>,
+    errors: Vec<AccountError<'a, D>>,
+}
+
+impl<D> Default for Accounts<'_, D> {
+    fn default() -> Self {
+        Self {
+            dates: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<'a, D> Accounts<'a, D> {
+    /// Returns true if the account is open, and report an error otherwise
+    fn check(&mut self, directive: &'a Directive<Number<D>>, account: &Account) -> bool {
+        let kind = match self.dates.get(account) {
+            None => AccountErrorKind::NotOpen,
+            Some((_, Some(closed))) => AccountErrorKind::Closed(*closed),
+            Some((_, None)) => return true,
+        };
+        self.report(directive, account, kind);
+        false
+    }
+
+    fn report(
+        &mut self,
+        directive: &'a Directive<Number<D>>,
+        account: &Account,
+        kind: AccountErrorKind,
+    ) {
+        self.errors.push(AccountError {
+            directive,
+            account: account.clone(),
+            kind,
+        });
+    }
+}
+
+/// Returns the transaction padding the account of the balance assertion, at the date of the pad directive
+fn padding<D: Decimal + Default + Display>(
+    pad_directive: &Directive<Number<D>>,
+    balance: &Balance<Number<D>>,
+    difference: Number<D>,
+) -> Directive<Number<D>> {
+    let DirectiveContent::Pad(pad) = &pad_directive.content else {
+        unreachable!("not a pad directive");
+    };
+    let currency = &balance.amount.currency;
+    let mut transaction = Transaction {
+        flag: Some('P'),
+        narration: Some(format!(
+            "(Padding inserted for Balance of {} {currency} for difference {difference} {currency})",
+            balance.amount.value
+        )),
+        ..Transaction::default()
+    };
+    let mut posting = Posting::from_account(pad.account.clone());
+    posting.amount = Some(Amount {
+        value: difference.clone(),
+        currency: currency.clone(),
+    });
+    transaction.postings.push(posting);
+    let mut posting = Posting::from_account(pad.source_account.clone());
+    posting.amount = Some(Amount {
+        value: -difference,
+        currency: currency.clone(),
+    });
+    transaction.postings.push(posting);
+    Directive {
+        date: pad_directive.date,
+        content: DirectiveContent::Transaction(transaction),
+        metadata: pad_directive.metadata.clone(),
+        line_number: pad_directive.line_number,
+        file: pad_directive.file.clone(),
+    }
+}
+
+/// Returns the tolerance of the balance assertion
+///
+/// It is the explicit tolerance, or twice the tolerance inferred from the precision of the amount
+/// (zero for integers, or if it overflows).
+fn balance_tolerance<D: Decimal + Default>(
+    balance: &Balance<Number<D>>,
+    tolerances: &Tolerances<D>,
+) -> D {
+    match (&balance.tolerance, balance.amount.value.precision()) {
+        (Some(tolerance), _) => tolerance.value().clone(),
+        (None, Some(precision)) => tolerances
+            .inferred(precision)
+            .zip("2".parse::<D>().ok())
+            .and_then(|(tolerance, two)| tolerance.checked_mul(two))
+            .unwrap_or_default(),
+        (None, None) => D::default(),
+    }
+}
+
+/// Sort the directives in the order of beancount
+///
+/// The directives are sorted by date, and for each date the `open` directives come first,
+/// then the `balance` directives, then the other directives, and the `close` directives last.
+/// The sort is stable, so that the directives of the same date and type keep their order.
+///
+/// This is the order in which [`check_balances`], [`check_accounts`] and [`expand_pads`] process the directives.
+///
+/// # Example
+///
+/// ```
+/// use beancount_parser::{parse, sort_directives, DirectiveContent};
+/// let input = r#"
+/// 2024-01-02 close Assets:Cash
+/// 2024-01-02 balance Assets:Cash 0 CHF
+/// 2024-01-01 open Assets:Cash
+/// "#;
+/// let mut directives = parse::<f64>(input).unwrap().directives;
+/// sort_directives(&mut directives);
+/// let lines: Vec<u32> = directives.iter().map(|d| d.line_number).collect();
+/// assert_eq!(lines, [4, 3, 2]);
+/// ```
+pub fn sort_directives<D>(directives: &mut [Directive<D>]) {
+    directives.sort_by_key(|directive| (directive.date, order(&directive.content)));
+}
+
+/// Returns the directives in the order of beancount, like [`sort_directives`]
+///
+/// The sort is stable, so that the directives of the same date and type keep their order.
+pub(crate) fn sorted<D>(directives: &[Directive<D>]) -> Vec<&Directive<D>> {
+    sorted_indices(directives)
+        .into_iter()
+        .map(|index| &directives[index])
+        .collect()
+}
+
+fn sorted_indices<D>(directives: &[Directive<D>]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..directives.len()).collect();
+    indices.sort_by_key(|&index| {
+        let directive = &directives[index];
+        (directive.date, order(&directive.content))
+    });
+    indices
+}
+
+fn order<D>(content: &DirectiveContent<D>) -> i8 {
+    match content {
+        DirectiveContent::Open(_) => -2,
+        DirectiveContent::Balance(_) => -1,
+        DirectiveContent::Close(_) => 2,
+        _ => 0,
+    }
+}
+
+/// Units held by the accounts
+struct Ledger<D> {
+    booking: BookingEngine<Number<D>>,
+    inventories: HashMap<Account, Inventory<Number<D>>>,
+}
+
+impl<D: Decimal + PartialOrd + Default> Ledger<D> {
+    fn new(options: &[BeanOption]) -> Self {
+        Self {
+            booking: BookingEngine::from_options(options),
+            inventories: HashMap::new(),
+        }
+    }
+
+    fn apply(&mut self, directive: &Directive<Number<D>>) {
+        match &directive.content {
+            // The accounts with an unknown booking method use the default booking method
+            DirectiveContent::Open(open) => {
+                let _ = self.booking.open(open);
+            }
+            DirectiveContent::Transaction(transaction) => {
+                // If the transaction cannot be booked, the postings having an amount are still applied
+                let transaction = self
+                    .booking
+                    .book(directive.date, transaction)
+                    .unwrap_or_else(|_| {
+                        let mut transaction = transaction.clone();
+                        let _ = transaction.interpolate();
+                        transaction
+                    });
+                // The positions whose units overflow the inventory are ignored
+                for posting in &transaction.postings {
+                    if let Some(position) = Position::from_posting(posting) {
+                        let _ = self
+                            .inventories
+                            .entry(posting.account.clone())
+                            .or_default()
+                            .add(position);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the units of the currency held by the account, not including its sub-accounts
+    ///
+    /// Returns `None` if the units overflow.
+    fn account_units(&self, account: &Account, currency: &str) -> Option<Number<D>> {
+        self.inventories
+            .get(account)
+            .map_or(Ok(Number::default()), |inventory| inventory.units(currency))
+            .ok()
+    }
+
+    /// Returns the units of the currency held by the account and its sub-accounts
+    ///
+    /// Returns `None` if the units overflow.
+    fn units(&self, account: &Account, currency: &str) -> Option<D> {
+        let prefix = format!("{account}:");
+        self.inventories
+            .iter()
+            .filter(|(candidate, _)| {
+                *candidate == account || candidate.as_str().starts_with(&prefix)
+            })
+            .try_fold(D::default(), |total, (_, inventory)| {
+                total.checked_add(inventory.units(currency).ok()?.into_value())
+            })
+    }
+}
+
+/// Failed balance assertion, returned by [`check_balances`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct BalanceError<'a, D> {
+    /// The `balance` directive
+    pub directive: &'a Directive<Number<D>>,
+    /// Amount asserted by the directive
+    pub expected: Amount<D>,
+    /// Units held by the account and its sub-accounts at the beginning of the date of the directive
+    ///
+    /// It is `None` if the units overflow.
+    pub actual: Option<Amount<D>>,
+    /// Actual units minus expected units
+    ///
+    /// It is `None` if the actual units, or the difference, overflow.
+    pub difference: Option<D>,
+    /// Tolerance of the balance assertion
+    pub tolerance: D,
+}
+
+impl<D: Display> Display for BalanceError<'_, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let account = match &self.directive.content {
+            DirectiveContent::Balance(balance) => balance.account.as_str(),
+            _ => "",
+        };
+        let currency = &self.expected.currency;
+        let expected = &self.expected.value;
+        match (&self.actual, &self.difference) {
+            (Some(actual), Some(difference)) => write!(
+                f,
+                "Balance failed for {account}: expected {expected} {currency}, but was {} {currency} (difference: {difference} {currency})",
+                actual.value,
+            ),
+            (Some(actual), None) => write!(
+                f,
+                "Balance failed for {account}: expected {expected} {currency}, but was {} {currency} (the difference overflows)",
+                actual.value,
+            ),
+            (None, _) => write!(
+                f,
+                "Balance failed for {account}: expected {expected} {currency}, but the units of the account overflow",
+            ),
+        }
+    }
+}
+
+impl<D: std::fmt::Debug + Display> std::error::Error for BalanceError<'_, D> {}
+
+/// Unused `pad` directive, returned by [`expand_pads`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PadError<'a, D> {
+    /// The `pad` directive
+    pub directive: &'a Directive<Number<D>>,
+}
+
+impl<D> Display for PadError<'_, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unused pad directive")
+    }
+}
+
+impl<D: std::fmt::Debug> std::error::Error for PadError<'_, D> {}
+
+/// Account referenced outside of its open interval, opened twice, or closed while not empty, returned by [`check_accounts`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AccountError<'a, D> {
+    /// The directive referencing the account
+    pub directive: &'a Directive<Number<D>>,
+    /// The referenced account
+    pub account: Account,
+    /// Kind of error
+    pub kind: AccountErrorKind,
+}
+
+/// Kind of [`AccountError`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AccountErrorKind {
+    /// The account is not open at the date of the directive
+    NotOpen,
+    /// The account was closed at the given date
+    Closed(Date),
+    /// The `open` directive is for an account already opened at the given date
+    AlreadyOpen(Date),
+    /// The `close` directive is for an account that still holds units
+    NotEmpty,
+}
+
+impl<D> Display for AccountError<'_, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let account = &self.account;
+        match self.kind {
+            AccountErrorKind::NotOpen => write!(f, "Account {account} is not open"),
+            AccountErrorKind::Closed(date) => write!(
+                f,
+                "Account {account} is closed since {:04}-{:02}-{:02}",
+                date.year, date.month, date.day
+            ),
+            AccountErrorKind::AlreadyOpen(date) => write!(
+                f,
+                "Account {account} is already open since {:04}-{:02}-{:02}",
+                date.year, date.month, date.day
+            ),
+            AccountErrorKind::NotEmpty => write!(f, "Account {account} is not empty"),
+        }
+    }
+}
+
+impl<D: std::fmt::Debug> std::error::Error for AccountError<'_, D> {}
@@ -0,0 +1,352 @@
+#![allow(clippy::pedantic, missing_docs)]
+
+use rstest::rstest;
+use rust_decimal::Decimal;
+
+use beancount_parser::{
+    parse, BookingEngine, BookingError, BookingMethod, DirectiveContent, InterpolationError,
+    Position, Posting, Transaction,
+};
+
+/// Books the transactions of the input, and returns the engine with the result of the last booking
+fn book(
+    input: &str,
+) -> (
+    BookingEngine<Decimal>,
+    Result<Transaction<Decimal>, BookingError<Decimal>>,
+) {
+    let beancount = parse::<Decimal>(input).unwrap();
+    let mut engine = BookingEngine::from_options(&beancount.options);
+    let mut result = None;
+    for directive in beancount.directives {
+        match &directive.content {
+            // The accounts with an unknown booking method use the default booking method
+            DirectiveContent::Open(open) => {
+                let _ = engine.open(open);
+            }
+            DirectiveContent::Transaction(trx) => {
+                result = Some(engine.book(directive.date, trx));
+            }
+            _ => (),
+        }
+    }
+    (engine, result.expect("no transaction"))
+}
+
+fn describe_postings(postings: &[Posting<Decimal>]) -> Vec<String> {
+    postings
+        .iter()
+        .map(|posting| {
+            let position = Position::from_posting(posting).unwrap();
+            format!("{} {position}", posting.account)
+        })
+        .collect()
+}
+
+const LOTS: &str = r#"
+2024-01-01 open Assets:Broker "METHOD"
+2024-01-01 open Assets:Cash
+2024-01-02 *
+  Assets:Broker  10 ACME {100 USD}
+  Assets:Cash
+2024-01-03 *
+  Assets:Broker  5 ACME {120 USD, "b"}
+  Assets:Cash
+2024-01-04 *
+  Assets:Broker  10 ACME {110 USD}
+  Assets:Cash
+"#;
+
+fn with_lots(method: &str, reduction: &str) -> String {
+    format!(
+        "{}2024-02-01 *\n  Assets:Broker  {reduction}\n  Assets:Cash\n",
+        LOTS.replace("METHOD", method)
+    )
+}
+
+#[rstest]
+#[case::strict_by_cost("STRICT", "-4 ACME {100 USD}", &["Assets:Broker -4 ACME {100 USD, 2024-01-02}"])]
+#[case::strict_by_date("STRICT", "-4 ACME {2024-01-04}", &["Assets:Broker -4 ACME {110 USD, 2024-01-04}"])]
+#[case::strict_by_label("STRICT", "-4 ACME {\"b\"}", &["Assets:Broker -4 ACME {120 USD, 2024-01-03, \"b\"}"])]
+#[case::strict_all_lots(
+    "STRICT",
+    "-25 ACME {}",
+    &[
+        "Assets:Broker -10 ACME {100 USD, 2024-01-02}",
+        "Assets:Broker -5 ACME {120 USD, 2024-01-03, \"b\"}",
+        "Assets:Broker -10 ACME {110 USD, 2024-01-04}",
+    ]
+)]
+#[case::strict_with_size(
+    "STRICT_WITH_SIZE",
+    "-5 ACME {}",
+    &["Assets:Broker -5 ACME {120 USD, 2024-01-03, \"b\"}"]
+)]
+#[case::strict_with_size_oldest(
+    "STRICT_WITH_SIZE",
+    "-10 ACME {}",
+    &["Assets:Broker -10 ACME {100 USD, 2024-01-02}"]
+)]
+#[case::fifo(
+    "FIFO",
+    "-12 ACME {}",
+    &[
+        "Assets:Broker -10 ACME {100 USD, 2024-01-02}",
+        "Assets:Broker -2 ACME {120 USD, 2024-01-03, \"b\"}",
+    ]
+)]
+#[case::lifo(
+    "LIFO",
+    "-12 ACME {}",
+    &[
+        "Assets:Broker -10 ACME {110 USD, 2024-01-04}",
+        "Assets:Broker -2 ACME {120 USD, 2024-01-03, \"b\"}",
+    ]
+)]
+#[case::hifo(
+    "HIFO",
+    "-12 ACME {}",
+    &[
+        "Assets:Broker -5 ACME {120 USD, 2024-01-03, \"b\"}",
+        "Assets:Broker -7 ACME {110 USD, 2024-01-04}",
+    ]
+)]
+#[case::fifo_with_cost("FIFO", "-4 ACME {110 USD}", &["Assets:Broker -4 ACME {110 USD, 2024-01-04}"])]
+#[case::average("AVERAGE", "-5 ACME {}", &["Assets:Broker -5 ACME {108 USD, 2024-02-01}"])]
+#[case::none("NONE", "-5 ACME {90 USD}", &["Assets:Broker -5 ACME {90 USD}"])]
+fn should_book_reduction(#[case] method: &str, #[case] reduction: &str, #[case] expected: &[&str]) {
+    let (_, result) = book(&with_lots(method, reduction));
+    let booked = result.unwrap();
+    let reductions: Vec<_> = booked
+        .postings
+        .iter()
+        .filter(|posting| posting.account.as_str() == "Assets:Broker")
+        .cloned()
+        .collect();
+    assert_eq!(describe_postings(&reductions), expected);
+}
+
+#[rstest]
+#[case::fifo("FIFO", "-12 ACME {}", &["3 ACME {120 USD, 2024-01-03, \"b\"}", "10 ACME {110 USD, 2024-01-04}"])]
+#[case::average("AVERAGE", "-5 ACME {}", &["20 ACME {108 USD, 2024-02-01}"])]
+#[case::none(
+    "NONE",
+    "-5 ACME {90 USD}",
+    &["10 ACME {100 USD}", "5 ACME {120 USD, \"b\"}", "10 ACME {110 USD}", "-5 ACME {90 USD}"]
+)]
+fn should_update_inventory(
+    #[case] method: &str,
+    #[case] reduction: &str,
+    #[case] expected: &[&str],
+) {
+    let (engine, result) = book(&with_lots(method, reduction));
+    result.unwrap();
+    let inventory: Vec<String> = engine
+        .inventory("Assets:Broker")
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(inventory, expected);
+}
+
+#[rstest]
+#[case::option(
+    "option \"booking_method\" \"LIFO\"\n",
+    "",
+    &["Assets:Broker -10 ACME {110 USD, 2024-01-04}", "Assets:Broker -2 ACME {120 USD, 2024-01-03, \"b\"}"]
+)]
+#[case::account_over_option(
+    "option \"booking_method\" \"LIFO\"\n",
+    "FIFO",
+    &["Assets:Broker -10 ACME {100 USD, 2024-01-02}", "Assets:Broker -2 ACME {120 USD, 2024-01-03, \"b\"}"]
+)]
+#[case::unknown_method(
+    "option \"booking_method\" \"LIFO\"\n",
+    "FIFOO",
+    &["Assets:Broker -10 ACME {110 USD, 2024-01-04}", "Assets:Broker -2 ACME {120 USD, 2024-01-03, \"b\"}"]
+)]
+fn should_use_default_booking_method(
+    #[case] options: &str,
+    #[case] method: &str,
+    #[case] expected: &[&str],
+) {
+    let input = with_lots(method, "-12 ACME {}").replace(" \"\"\n", "\n");
+    let (_, result) = book(&format!("{options}{input}"));
+    let booked = result.unwrap();
+    assert_eq!(describe_postings(&booked.postings[..2]), expected);
+}
+
+#[rstest]
+#[case("", BookingMethod::Strict)]
+#[case("option \"booking_method\" \"HIFO\"", BookingMethod::Hifo)]
+#[case("option \"booking_method\" \"FIFOO\"", BookingMethod::Strict)]
+fn should_use_known_booking_method_option(#[case] input: &str, #[case] expected: BookingMethod) {
+    let beancount = parse::<Decimal>(input).unwrap();
+    let engine = BookingEngine::<Decimal>::from_options(&beancount.options);
+    assert_eq!(engine.default_method(), &expected);
+}
+
+#[rstest]
+#[case::option("option \"booking_method\" \"FIFOO\"")]
+#[case::last_option("option \"booking_method\" \"FIFO\"\noption \"booking_method\" \"fifo\"")]
+fn should_reject_unknown_booking_method_option(#[case] input: &str) {
+    let beancount = parse::<Decimal>(input).unwrap();
+    let error = BookingEngine::<Decimal>::try_from_options(&beancount.options).unwrap_err();
+    assert!(!error.method.is_known());
+}
+
+#[test]
+fn should_report_unknown_booking_method_of_account() {
+    let beancount = parse::<Decimal>(
+        "2024-01-01 open Assets:Broker \"FIFOO\"\n2024-01-01 open Assets:Cash \"FIFO\"",
+    )
+    .unwrap();
+    let mut engine = BookingEngine::<Decimal>::new();
+    let results: Vec<_> = beancount
+        .directives
+        .iter()
+        .map(|directive| {
+            let DirectiveContent::Open(open) = &directive.content else {
+                panic!("was not an open directive");
+            };
+            engine.open(open).map_err(|error| error.to_string())
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [Err("Unknown booking method FIFOO".to_owned()), Ok(())]
+    );
+}
+
+#[test]
+fn should_interpolate_booked_transaction() {
+    let (engine, result) = book(&with_lots("FIFO", "-12 ACME {}"));
+    let booked = result.unwrap();
+    assert_eq!(
+        describe_postings(&booked.postings[2..]),
+        ["Assets:Cash 1240 USD"]
+    );
+    assert_eq!(
+        engine.inventory("Assets:Cash").unwrap().units("USD"),
+        Ok(Decimal::from(-1000 - 600 - 1100 + 1240))
+    );
+}
+
+#[test]
+fn should_date_augmentations() {
+    let (engine, result) = book(LOTS);
+    result.unwrap();
+    let dates: Vec<String> = engine
+        .inventory("Assets:Broker")
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        dates,
+        [
+            "10 ACME {100 USD, 2024-01-02}",
+            "5 ACME {120 USD, 2024-01-03, \"b\"}",
+            "10 ACME {110 USD, 2024-01-04}"
+        ]
+    );
+}
+
+#[rstest]
+#[case::other_cost("STRICT", "-4 ACME {90 USD}")]
+#[case::other_label("FIFO", "-4 ACME {\"c\"}")]
+fn should_not_book_without_matching_lot(#[case] method: &str, #[case] reduction: &str) {
+    let (_, result) = book(&with_lots(method, reduction));
+    let Err(BookingError::NoMatchingLot(posting)) = result else {
+        panic!("unexpected result: {result:?}");
+    };
+    assert_eq!(posting.account.as_str(), "Assets:Broker");
+}
+
+#[rstest]
+#[case::strict("STRICT", "-4 ACME {}", 4)]
+#[case::strict_with_size("STRICT_WITH_SIZE", "-4 ACME {}", 4)]
+#[case::same_cost("STRICT", "-4 ACME {100 USD}", 2)]
+fn should_not_book_ambiguous_match(
+    #[case] method: &str,
+    #[case] reduction: &str,
+    #[case] expected_lots: usize,
+) {
+    let input = format!(
+        "{}2024-01-05 *\n  Assets:Broker  1 ACME {{100 USD}}\n  Assets:Cash\n2024-02-01 *\n  Assets:Broker  {reduction}\n  Assets:Cash\n",
+        LOTS.replace("METHOD", method)
+    );
+    let (_, result) = book(&input);
+    let Err(BookingError::AmbiguousMatch(posting, lots)) = result else {
+        panic!("unexpected result: {result:?}");
+    };
+    assert_eq!(posting.account.as_str(), "Assets:Broker");
+    assert_eq!(lots.len(), expected_lots);
+}
+
+#[rstest]
+#[case::strict("STRICT", "-11 ACME {100 USD}")]
+#[case::fifo("FIFO", "-26 ACME {}")]
+fn should_not_book_insufficient_units(#[case] method: &str, #[case] reduction: &str) {
+    let (engine, result) = book(&with_lots(method, reduction));
+    let Err(BookingError::InsufficientUnits(posting, _)) = result else {
+        panic!("unexpected result: {result:?}");
+    };
+    assert_eq!(posting.account.as_str(), "Assets:Broker");
+    assert_eq!(
+        engine.inventory("Assets:Broker").unwrap().units("ACME"),
+        Ok(Decimal::from(25))
+    );
+}
+
+#[test]
+fn should_not_book_overflowing_units() {
+    let input = r#"
+2024-01-01 open Assets:Broker
+2024-01-01 open Assets:Cash
+2024-01-02 *
+  Assets:Broker  79228162514264337593543950335 ACME
+  Assets:Cash   -79228162514264337593543950335 ACME
+2024-01-03 *
+  Assets:Broker  1 ACME
+  Assets:Cash   -1 ACME
+"#;
+    let (engine, result) = book(input);
+    let Err(BookingError::Overflow(posting)) = result else {
+        panic!("unexpected result: {result:?}");
+    };
+    assert_eq!(posting.account.as_str(), "Assets:Broker");
+    assert_eq!(
+        engine.inventory("Assets:Cash").unwrap().units("ACME"),
+        Ok(-Decimal::MAX)
+    );
+}
+
+#[rstest]
+#[case::strict("STRICT", "1 ACME {100 USD}\n  Assets:Broker  -4 ACME {90 USD}")]
+#[case::average("AVERAGE", "-26 ACME {}")]
+fn should_leave_inventories_unchanged_on_error(#[case] method: &str, #[case] postings: &str) {
+    let (engine, result) = book(&with_lots(method, postings));
+    assert!(result.is_err());
+    let (expected, _) = book(&LOTS.replace("METHOD", method));
+    assert_eq!(
+        engine.inventory("Assets:Broker"),
+        expected.inventory("Assets:Broker")
+    );
+    assert_eq!(
+        engine.inventory("Assets:Cash"),
+        expected.inventory("Assets:Cash")
+    );
+}
+
+#[test]
+fn should_not_book_ambiguous_interpolation() {
+    let (_, result) = book("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:B\n  Assets:C");
+    assert_eq!(
+        result,
+        Err(BookingError::Interpolation(
+            InterpolationError::AmbiguousPostings(vec![1, 2])
+        ))
+    );
+}
@@ -0,0 +1,128 @@
+#![allow(missing_docs)]
+
+use std::ops::Range;
+
+use rstest::rstest;
+
+use beancount_parser::{parse_iter, Entry, IncrementalParse};
+
+const OFFICIAL: &str = include_str!("samples/official.beancount");
+
+fn expected(input: &str) -> (Vec<Entry<f64>>, Option<u32>) {
+    let mut entries = Vec::new();
+    for entry in parse_iter::<f64>(input) {
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(err) => return (entries, Some(err.line_number())),
+        }
+    }
+    (entries, None)
+}
+
+fn actual(parse: &IncrementalParse<f64>) -> (Vec<Entry<f64>>, Option<u32>) {
+    (
+        parse.entries().to_vec(),
+        parse.error().map(|err| err.line_number()),
+    )
+}
+
+/// Apply the edit, and check the result against a full parse of the edited source
+fn check_edit(parse: &mut IncrementalParse<f64>, range: Range<usize>, replacement: &str) {
+    let before = parse.entries().to_vec();
+    let change = parse.edit(range.clone(), replacement);
+    let (entries, error) = expected(parse.source());
+    assert_eq!(
+        actual(parse),
+        (entries, error),
+        "after replacing {range:?} by {replacement:?}"
+    );
+    let after = parse.entries();
+    assert_eq!(
+        before[..change.removed.start],
+        after[..change.inserted.start]
+    );
+    assert_eq!(
+        before.len() - change.removed.end,
+        after.len() - change.inserted.end
+    );
+}
+
+#[rstest]
+#[case::insert_posting("2024-01-01 *\n  Assets:A 1 CHF\n  Assets:B\n", 41..41, "  Assets:C\n")]
+#[case::insert_after_comment("2024-01-01 *\n; comment\n2024-01-02 *\n", 23..23, "  a: 1\n")]
+#[case::open_string("2024-01-01 *\n2024-01-02 *\n2024-01-03 *\n", 24..24, " \"a")]
+#[case::close_string("2024-01-01 * \"a\n2024-01-02 *\n2024-01-03 *\n", 27..27, "\"")]
+#[case::push_tag("2024-01-01 *\n2024-01-02 *\n", 0..0, "pushtag #a\n")]
+#[case::pop_tag("pushtag #a\n2024-01-01 *\npoptag #a\n2024-01-02 *\n", 24..34, "")]
+#[case::introduce_error("2024-01-01 *\n2024-01-02 *\n2024-01-03 *\n", 13..23, "oops")]
+#[case::fix_error("2024-01-01 *\noops\n2024-01-03 *\n", 13..17, "2024-01-02 *")]
+#[case::edit_after_error("2024-01-01 *\noops\n2024-01-03 *\n", 18..18, "\n")]
+#[case::edit_before_error("2024-01-01 *\n2024-01-02 *\noops\n", 0..0, "\n\n")]
+#[case::delete_line_break("2024-01-01 *\n2024-01-02 *\n", 12..13, "")]
+#[case::replace_everything("2024-01-01 *\n", 0..13, "2024-01-02 open A\n")]
+#[case::empty("", 0..0, "2024-01-02 open A")]
+fn should_update_entries_like_full_parse(
+    #[case] source: &str,
+    #[case] range: Range<usize>,
+    #[case] replacement: &str,
+) {
+    let mut parse = IncrementalParse::<f64>::new(source);
+    assert_eq!(actual(&parse), expected(source));
+    check_edit(&mut parse, range, replacement);
+}
+
+#[rstest]
+fn should_only_report_edited_directive_as_changed() {
+    let mut parse = IncrementalParse::<f64>::new(OFFICIAL);
+    let offset = OFFICIAL.find("2023-08-09 * \"EDISON POWER\"").unwrap();
+    let line_number = u32::try_from(OFFICIAL[..offset].lines().count()).unwrap() + 1;
+    let index = parse
+        .entries()
+        .iter()
+        .position(|entry| matches!(entry, Entry::Directive(d) if d.line_number == line_number))
+        .unwrap();
+    let change = parse.edit(offset + 3..offset + 4, "4");
+    assert_eq!(change.removed, index..index + 1);
+    assert_eq!(change.inserted, index..index + 1);
+}
+
+#[rstest]
+fn should_be_like_full_parse_after_many_edits() {
+    const SNIPPETS: [&str; 12] = [
+        "",
+        "\n",
+        "  ",
+        "\"",
+        "; ",
+        "pushtag #a\n",
+        "poptag #a\n",
+        "2024-01-01 *\n",
+        "  Assets:Cash 1 CHF\n",
+        "  key: \"value\"\n",
+        "oops",
+        "\\",
+    ];
+    let mut source: String = OFFICIAL.lines().take(400).collect::<Vec<_>>().join("\n");
+    source.push('\n');
+    let mut parse = IncrementalParse::<f64>::new(source);
+    let mut seed: u64 = 42;
+    let mut random = |max: usize| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        usize::try_from(seed >> 33).unwrap() % max
+    };
+    for _ in 0..300 {
+        let len = parse.source().len();
+        let mut start = random(len + 1);
+        while !parse.source().is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (start + random(20)).min(len);
+        while !parse.source().is_char_boundary(end) {
+            end -= 1;
+        }
+        let replacement = SNIPPETS[random(SNIPPETS.len())];
+        check_edit(&mut parse, start..end, replacement);
+    }
+}
@@ -0,0 +1,134 @@
+#![allow(clippy::pedantic, missing_docs)]
+
+use rstest::rstest;
+use rust_decimal::Decimal;
+
+use beancount_parser::{parse, DirectiveContent, InterpolationError, Transaction};
+
+fn transaction(input: &str) -> Transaction<Decimal> {
+    let directive = parse::<Decimal>(input).unwrap().directives.remove(0);
+    let DirectiveContent::Transaction(trx) = directive.content else {
+        panic!("was not a transaction");
+    };
+    trx
+}
+
+fn postings(trx: &Transaction<Decimal>) -> Vec<String> {
+    trx.postings
+        .iter()
+        .map(|posting| match &posting.amount {
+            Some(amount) => format!("{} {} {}", posting.account, amount.value, amount.currency),
+            None => posting.account.to_string(),
+        })
+        .collect()
+}
+
+#[rstest]
+#[case::no_posting("2024-01-01 *", &[])]
+#[case::single_currency(
+    "2024-01-01 *\n  Expenses:Food 10.00 CHF\n  Assets:Cash -8 CHF\n  Assets:Card 1.5 CHF",
+    &["3.50 CHF"]
+)]
+#[case::multiple_currencies(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Expenses:Fuel 20 EUR\n  Assets:Cash -10 CHF",
+    &["0 CHF", "20 EUR"]
+)]
+#[case::unit_price(
+    "2024-01-01 *\n  Assets:Cash 10 EUR @ 1.10 CHF\n  Assets:Bank -10 CHF",
+    &["1.00 CHF"]
+)]
+#[case::total_price(
+    "2024-01-01 *\n  Assets:Cash -10 EUR @@ 11.00 CHF\n  Assets:Bank 11 CHF",
+    &["0.00 CHF"]
+)]
+#[case::cost_over_price(
+    "2024-01-01 *\n  Assets:Stock 2 ACME {100 CHF} @ 120 CHF\n  Assets:Bank -150 CHF",
+    &["50 CHF"]
+)]
+#[case::ignores_postings_without_amount(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash",
+    &["10 CHF"]
+)]
+fn should_compute_residual(#[case] input: &str, #[case] expected: &[&str]) {
+    let residual: Vec<String> = transaction(input)
+        .residual()
+        .unwrap()
+        .into_iter()
+        .map(|amount| format!("{} {}", amount.value, amount.currency))
+        .collect();
+    assert_eq!(residual, expected);
+}
+
+#[rstest]
+#[case::complete(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash -10 CHF",
+    &["Expenses:Food 10 CHF", "Assets:Cash -10 CHF"]
+)]
+#[case::single_currency(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash",
+    &["Expenses:Food 10 CHF", "Assets:Cash -10 CHF"]
+)]
+#[case::in_the_middle(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash\n  Expenses:Fuel 20 CHF",
+    &["Expenses:Food 10 CHF", "Assets:Cash -30 CHF", "Expenses:Fuel 20 CHF"]
+)]
+#[case::multiple_currencies(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Expenses:Fuel 20 EUR\n  Assets:Cash",
+    &["Expenses:Food 10 CHF", "Expenses:Fuel 20 EUR", "Assets:Cash -10 CHF", "Assets:Cash -20 EUR"]
+)]
+#[case::price(
+    "2024-01-01 *\n  Assets:Cash 10 EUR @ 1.10 CHF\n  Assets:Bank",
+    &["Assets:Cash 10 EUR", "Assets:Bank -11.00 CHF"]
+)]
+#[case::cost(
+    "2024-01-01 *\n  Assets:Stock 2 ACME {100 CHF}\n  Assets:Bank",
+    &["Assets:Stock 2 ACME", "Assets:Bank -200 CHF"]
+)]
+#[case::balanced(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash -10 CHF\n  Assets:Other",
+    &["Expenses:Food 10 CHF", "Assets:Cash -10 CHF"]
+)]
+fn should_interpolate_posting_without_amount(#[case] input: &str, #[case] expected: &[&str]) {
+    let mut trx = transaction(input);
+    trx.interpolate().unwrap();
+    assert_eq!(postings(&trx), expected);
+}
+
+#[test]
+fn should_keep_posting_flag_and_metadata() {
+    let mut trx = transaction(
+        "2024-01-01 *\n  Expenses:Food 10 CHF\n  Expenses:Fuel 20 EUR\n  ! Assets:Cash\n    note: \"wallet\"",
+    );
+    trx.interpolate().unwrap();
+    for posting in &trx.postings[2..] {
+        assert_eq!(posting.flag, Some('!'));
+        assert!(posting.metadata.contains_key("note"));
+    }
+}
+
+#[rstest]
+#[case::ambiguous(
+    "2024-01-01 *\n  Expenses:Food 10 CHF\n  Assets:Cash\n  Assets:Card",
+    InterpolationError::AmbiguousPostings(vec![1, 2])
+)]
+#[case::incomplete_cost(
+    "2024-01-01 *\n  Assets:Stock -2 ACME {}\n  Assets:Bank",
+    InterpolationError::IncompletePosting(0)
+)]
+#[case::overflowing_weight(
+    "2024-01-01 *\n  Assets:Cash 79228162514264337593543950335 USD @ 2 EUR\n  Assets:Bank",
+    InterpolationError::Overflow(0)
+)]
+#[case::overflowing_sum(
+    "2024-01-01 *\n  Assets:Cash 79228162514264337593543950335 USD\n  Assets:Cash 1 USD\n  Assets:Bank",
+    InterpolationError::Overflow(1)
+)]
+fn should_reject_transaction_that_cannot_be_interpolated(
+    #[case] input: &str,
+    #[case] expected: InterpolationError,
+) {
+    let mut trx = transaction(input);
+    let before = trx.clone();
+    assert_eq!(trx.interpolate(), Err(expected));
+    assert_eq!(trx, before);
+}
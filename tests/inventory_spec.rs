@@ -0,0 +1,157 @@
+#![allow(clippy::pedantic, missing_docs)]
+
+use rstest::rstest;
+use rust_decimal::Decimal;
+
+use beancount_parser::{parse, DirectiveContent, Inventory, Position, ReductionError};
+
+/// Returns the positions of the postings of the transaction
+fn positions(input: &str) -> Vec<Position<Decimal>> {
+    let directive = parse::<Decimal>(input).unwrap().directives.remove(0);
+    let DirectiveContent::Transaction(trx) = directive.content else {
+        panic!("was not a transaction");
+    };
+    trx.postings
+        .iter()
+        .map(|posting| Position::from_posting(posting).unwrap())
+        .collect()
+}
+
+fn inventory(positions: Vec<Position<Decimal>>) -> Inventory<Decimal> {
+    let mut inventory = Inventory::new();
+    for position in positions {
+        inventory.add(position).unwrap();
+    }
+    inventory
+}
+
+fn describe(inventory: &Inventory<Decimal>) -> Vec<String> {
+    inventory.iter().map(ToString::to_string).collect()
+}
+
+#[rstest]
+#[case::same_currency("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A 5 CHF", &["15 CHF"])]
+#[case::different_currencies("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A 5 EUR", &["10 CHF", "5 EUR"])]
+#[case::negative_units("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A -15 CHF", &["-5 CHF"])]
+#[case::zero_units("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A -10 CHF", &[])]
+#[case::zero_position("2024-01-01 *\n  Assets:A 0 CHF", &[])]
+#[case::same_cost(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 5 ACME {100 USD}",
+    &["15 ACME {100 USD}"]
+)]
+#[case::different_costs(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 5 ACME {110 USD}\n  Assets:A 1 ACME",
+    &["10 ACME {100 USD}", "5 ACME {110 USD}", "1 ACME"]
+)]
+#[case::different_dates(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD, 2024-01-01}\n  Assets:A 5 ACME {100 USD, 2024-01-02}",
+    &["10 ACME {100 USD, 2024-01-01}", "5 ACME {100 USD, 2024-01-02}"]
+)]
+#[case::different_labels(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD, \"a\"}\n  Assets:A 5 ACME {100 USD, \"b\"}\n  Assets:A 1 ACME {100 USD, \"a\"}",
+    &["11 ACME {100 USD, \"a\"}", "5 ACME {100 USD, \"b\"}"]
+)]
+fn should_add_positions(#[case] input: &str, #[case] expected: &[&str]) {
+    let inventory = inventory(positions(input));
+    assert_eq!(describe(&inventory), expected);
+}
+
+#[rstest]
+#[case::partially("2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A -4 ACME {100 USD}", &["6 ACME {100 USD}"])]
+#[case::entirely("2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A -10 ACME {100 USD}", &[])]
+#[case::short_position("2024-01-01 *\n  Assets:A -10 ACME {100 USD}\n  Assets:A 4 ACME {100 USD}", &["-6 ACME {100 USD}"])]
+#[case::matching_lot(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 5 ACME {110 USD}\n  Assets:A -5 ACME {110 USD}",
+    &["10 ACME {100 USD}"]
+)]
+#[case::without_cost("2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A -3 CHF", &["7 CHF"])]
+fn should_reduce_lot(#[case] input: &str, #[case] expected: &[&str]) {
+    let mut positions = positions(input);
+    let reduction = positions.pop().unwrap();
+    let mut inventory = inventory(positions);
+    inventory.reduce(reduction).unwrap();
+    assert_eq!(describe(&inventory), expected);
+}
+
+#[rstest]
+#[case::empty(
+    "2024-01-01 *\n  Assets:A -4 ACME {100 USD}",
+    ReductionError::NoMatchingLot
+)]
+#[case::other_cost(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A -4 ACME {110 USD}",
+    ReductionError::NoMatchingLot
+)]
+#[case::other_currency(
+    "2024-01-01 *\n  Assets:A 10 CHF\n  Assets:A -4 EUR",
+    ReductionError::NoMatchingLot
+)]
+#[case::same_sign(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 4 ACME {100 USD}",
+    ReductionError::NoMatchingLot
+)]
+#[case::too_many_units(
+    "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A -11 ACME {100 USD}",
+    ReductionError::InsufficientUnits(positions("2024-01-01 *\n  Assets:A 10 ACME {100 USD}").remove(0))
+)]
+fn should_not_reduce_missing_lot(#[case] input: &str, #[case] expected: ReductionError<Decimal>) {
+    let mut positions = positions(input);
+    let reduction = positions.pop().unwrap();
+    let mut inventory = inventory(positions);
+    let before = inventory.clone();
+    assert_eq!(inventory.reduce(reduction), Err(expected));
+    assert_eq!(inventory, before);
+}
+
+#[test]
+fn should_compute_totals_per_currency() {
+    let inventory = inventory(positions(
+        "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 20 CHF\n  Assets:A 5 ACME {110 USD}\n  Assets:A -2 ACME",
+    ));
+    let totals: Vec<String> = inventory
+        .totals()
+        .unwrap()
+        .into_iter()
+        .map(|amount| format!("{} {}", amount.value, amount.currency))
+        .collect();
+    assert_eq!(totals, ["13 ACME", "20 CHF"]);
+    assert_eq!(inventory.units("ACME"), Ok(Decimal::from(13)));
+    assert_eq!(inventory.units("EUR"), Ok(Decimal::ZERO));
+}
+
+#[test]
+fn should_merge_inventories() {
+    let mut inventory = inventory(positions(
+        "2024-01-01 *\n  Assets:A 10 ACME {100 USD}\n  Assets:A 20 CHF",
+    ));
+    let other = self::inventory(positions(
+        "2024-01-01 *\n  Assets:A -20 CHF\n  Assets:A 5 ACME {100 USD}\n  Assets:A 5 EUR",
+    ));
+    inventory.merge(other).unwrap();
+    assert_eq!(describe(&inventory), ["15 ACME {100 USD}", "5 EUR"]);
+}
+
+#[test]
+fn should_not_add_overflowing_units() {
+    let mut inventory = inventory(positions(
+        "2024-01-01 *\n  Assets:A 79228162514264337593543950335 CHF\n  Assets:A 5 EUR",
+    ));
+    let before = inventory.clone();
+    let position = positions("2024-01-01 *\n  Assets:A 1 CHF").remove(0);
+    assert!(inventory.add(position).is_err());
+    assert_eq!(inventory, before);
+    let other = self::inventory(positions(
+        "2024-01-01 *\n  Assets:A 5 EUR\n  Assets:A 1 CHF",
+    ));
+    assert!(inventory.merge(other).is_err());
+    assert_eq!(inventory, before);
+}
+
+#[test]
+fn should_not_compute_overflowing_totals() {
+    let inventory = inventory(positions(
+        "2024-01-01 *\n  Assets:A 79228162514264337593543950335 CHF\n  Assets:A 1 CHF {1 USD}",
+    ));
+    assert!(inventory.units("CHF").is_err());
+    assert!(inventory.totals().is_err());
+}
@@ -1,16 +1,41 @@
 #![allow(missing_docs)]
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use rstest::rstest;
 
-use beancount_parser::BeancountFile;
+use beancount_parser::{
+    fs::InMemoryFileSystem, parse, read_files_from, read_files_with_options, read_str_from,
+    read_str_with_options, BeancountFile, IncludeDiagnostic, IncludeOrder, Limits,
+    ReadFileErrorKind, ReadOptions,
+};
+
+#[rstest]
+fn directives_read_from_file_should_equal_parsed_directives() {
+    let content = "2024-01-01 open Assets:Cash\n2024-01-02 close Assets:Cash\n";
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", content);
+    let mut file = BeancountFile::<f64>::default();
+    read_files_from(
+        &fs,
+        ["/ledger/main.beancount".into()],
+        &ReadOptions::default(),
+        |entry| file.extend(Some(entry)),
+    )
+    .unwrap();
+    assert_eq!(
+        file.directives[0].file.as_deref(),
+        Some(Path::new("/ledger/main.beancount"))
+    );
+    assert_eq!(file.directives, parse::<f64>(content).unwrap().directives);
+}
 
 #[rstest]
 #[case("comments.beancount", 0, 0)]
 #[case("simple.beancount", 16, 0)]
 #[case("official.beancount", 1714, 0)]
 #[case("includes.beancount", 1731, 6)]
+#[case("glob.beancount", 2, 2)]
 fn can_parse_example_files(
     #[case] file_name: &str,
     #[case] expected_directive_count: usize,
@@ -36,3 +61,558 @@ fn can_parse_example_files(
         assert_eq!(file.includes.len(), expected_include_count);
     }
 }
+
+#[rstest]
+#[case::expression_depth(
+    "expressions.beancount",
+    |l: &mut Limits| l.max_expression_depth = Some(3),
+    "expression at line 4 is nested more than 3 levels deep"
+)]
+#[case::file_size(
+    "simple.beancount",
+    |l: &mut Limits| l.max_file_size = Some(100),
+    "file is larger than 100 bytes"
+)]
+#[case::included_files(
+    "includes.beancount",
+    |l: &mut Limits| l.max_included_files = Some(2),
+    "more than 2 files are included"
+)]
+#[case::line_length(
+    "simple.beancount",
+    |l: &mut Limits| l.max_line_length = Some(60),
+    "line 26 is longer than 60 bytes"
+)]
+fn should_report_exceeded_limit(
+    #[case] file_name: &str,
+    #[case] set_limit: fn(&mut Limits),
+    #[case] expected_message: &str,
+) {
+    let mut options = ReadOptions::default();
+    set_limit(&mut options.limits);
+    let path: PathBuf = ["tests", "samples", file_name].iter().collect();
+    let error = read_files_with_options::<f64, _>([path], &options, |_| ()).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains(expected_message), "{message}");
+}
+
+#[rstest]
+#[case::expression_depth(|l: &mut Limits| l.max_expression_depth = Some(4))]
+#[case::file_size(|l: &mut Limits| l.max_file_size = Some(200))]
+#[case::included_files(|l: &mut Limits| l.max_included_files = Some(0))]
+#[case::line_length(|l: &mut Limits| l.max_line_length = Some(50))]
+fn should_accept_input_within_limits(#[case] set_limit: fn(&mut Limits)) {
+    let mut options = ReadOptions::default();
+    set_limit(&mut options.limits);
+    let path: PathBuf = "tests/samples/expressions.beancount".into();
+    let mut file = BeancountFile::<f64>::default();
+    read_files_with_options([path], &options, |entry| file.extend(Some(entry))).unwrap();
+    assert_eq!(file.directives.len(), 2);
+}
+
+#[rstest]
+fn should_reject_include_outside_of_root() {
+    let mut options = ReadOptions::default();
+    options.root = Some("tests/samples/sandbox".into());
+    let path: PathBuf = "tests/samples/sandbox/escape.beancount".into();
+    let error = read_files_with_options::<f64, _>([path], &options, |_| ()).unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::OutsideRoot);
+    assert!(error.path().ends_with("simple.beancount"), "{error}");
+}
+
+#[rstest]
+fn should_accept_include_within_root() {
+    let mut options = ReadOptions::default();
+    options.root = Some("tests/samples".into());
+    let path: PathBuf = "tests/samples/sandbox/escape.beancount".into();
+    let mut file = BeancountFile::<f64>::default();
+    read_files_with_options([path], &options, |entry| file.extend(Some(entry))).unwrap();
+    assert_eq!(file.directives.len(), 16);
+}
+
+#[cfg(unix)]
+#[rstest]
+fn should_reject_symbolic_link_escaping_root() {
+    let root = std::env::temp_dir().join(format!("beancount-sandbox-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let link = root.join("link.beancount");
+    let _ = std::fs::remove_file(&link);
+    std::os::unix::fs::symlink(
+        std::fs::canonicalize("tests/samples/simple.beancount").unwrap(),
+        &link,
+    )
+    .unwrap();
+    let main = root.join("main.beancount");
+    std::fs::write(&main, "include \"link.beancount\"\n").unwrap();
+    let mut options = ReadOptions::default();
+    options.root = Some(root.clone());
+    let result = read_files_with_options::<f64, _>([main], &options, |_| ());
+    std::fs::remove_dir_all(&root).unwrap();
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::OutsideRoot);
+    assert!(!error.to_string().contains("simple.beancount"), "{error}");
+}
+
+#[rstest]
+#[allow(deprecated)]
+fn should_report_default_expression_depth_as_syntax_error() {
+    let dir = std::env::temp_dir().join(format!("beancount-depth-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.beancount");
+    let depth = 70;
+    let expression = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    std::fs::write(&path, format!("2023-05-27 price CHF {expression} PLN\n")).unwrap();
+    let result = beancount_parser::read_files::<f64, _>([path.clone()], |_| ());
+    let result_v2 = beancount_parser::read_files_v2::<f64, _>([path], |_| ());
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(result.unwrap_err().to_string(), "Syntax error");
+    assert_eq!(result_v2.unwrap_err().kind(), ReadFileErrorKind::Syntax);
+}
+
+#[rstest]
+fn should_expand_glob_include_in_alphabetical_order() {
+    let path: PathBuf = "tests/samples/glob.beancount".into();
+    let file = BeancountFile::<f64>::read_files([path]).unwrap();
+    let names: Vec<_> = file
+        .includes
+        .iter()
+        .map(|p| p.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["2024-01.beancount", "2024-02.beancount"]);
+}
+
+#[rstest]
+fn should_fail_for_glob_include_without_match() {
+    let path: PathBuf = "tests/samples/glob-no-match.beancount".into();
+    let error = BeancountFile::<f64>::read_files([path]).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("*.missing"), "{message}");
+}
+
+#[rstest]
+fn should_emit_included_entries_in_source_order() {
+    let mut options = ReadOptions::default();
+    options.include_order = IncludeOrder::Source;
+    let path: PathBuf = "tests/samples/order/main.beancount".into();
+    let mut accounts = Vec::new();
+    read_files_with_options::<f64, _>([path], &options, |entry| {
+        if let Some(open) = entry.as_directive().and_then(|d| d.content.as_open()) {
+            accounts.push(open.account.to_string());
+        }
+    })
+    .unwrap();
+    assert_eq!(
+        accounts,
+        [
+            "Assets:A",
+            "Assets:B",
+            "Assets:B:Nested",
+            "Assets:C",
+            "Assets:D",
+            "Assets:E"
+        ]
+    );
+}
+
+#[rstest]
+fn should_read_files_from_in_memory_file_system() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert(
+        "/ledger/main.beancount",
+        "include \"accounts/*.beancount\"\ninclude \"../shared/prices.beancount\"",
+    );
+    fs.insert("/ledger/accounts/b.beancount", "2024-01-01 open Assets:B");
+    fs.insert("/ledger/accounts/a.beancount", "2024-01-01 open Assets:A");
+    fs.insert("/shared/prices.beancount", "2024-01-01 price CHF 1 EUR");
+    let mut file = BeancountFile::<f64>::default();
+    read_files_from(
+        &fs,
+        ["/ledger/main.beancount".into()],
+        &ReadOptions::default(),
+        |entry| file.extend(Some(entry)),
+    )
+    .unwrap();
+    assert_eq!(file.directives.len(), 3);
+    assert_eq!(
+        file.includes,
+        [
+            PathBuf::from("/ledger/accounts/a.beancount"),
+            PathBuf::from("/ledger/accounts/b.beancount"),
+            PathBuf::from("/shared/prices.beancount"),
+        ]
+    );
+}
+
+#[rstest]
+#[case::current_dir("include \"./accounts/*.beancount\"", "/ledger/accounts/a.beancount")]
+#[case::parent_dir("include \"../shared/*.beancount\"", "/shared/prices.beancount")]
+fn should_expand_relative_glob_of_in_memory_file_system(
+    #[case] main: &str,
+    #[case] expected: &str,
+) {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", main);
+    fs.insert("/ledger/accounts/a.beancount", "2024-01-01 open Assets:A");
+    fs.insert("/shared/prices.beancount", "2024-01-01 price CHF 1 EUR");
+    let mut file = BeancountFile::<f64>::default();
+    read_files_from(
+        &fs,
+        ["/ledger/main.beancount".into()],
+        &ReadOptions::default(),
+        |entry| file.extend(Some(entry)),
+    )
+    .unwrap();
+    assert_eq!(file.directives.len(), 1);
+    assert_eq!(file.includes, [PathBuf::from(expected)]);
+}
+
+#[rstest]
+fn should_restrict_includes_of_in_memory_file_system_to_root() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", "include \"../secret.beancount\"");
+    fs.insert("/secret.beancount", "2024-01-01 open Assets:Secret");
+    let mut options = ReadOptions::default();
+    options.root = Some("/ledger".into());
+    let result =
+        read_files_from::<f64, _>(&fs, ["/ledger/main.beancount".into()], &options, |_| ());
+    assert_eq!(result.unwrap_err().kind(), ReadFileErrorKind::OutsideRoot);
+}
+
+#[rstest]
+#[case::existing_file("../secret.beancount", "/secret.beancount")]
+#[case::missing_file("../missing.beancount", "/missing.beancount")]
+#[case::glob("/secret/*", "/secret/*")]
+#[case::glob_with_parent_dir("../*/*.beancount", "/*/*.beancount")]
+fn should_reject_include_outside_of_root_without_accessing_it(
+    #[case] include: &str,
+    #[case] expected_path: &str,
+) {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", format!("include \"{include}\""));
+    fs.insert("/secret.beancount", "2024-01-01 open Assets:Secret");
+    fs.insert("/secret/listed.beancount", "2024-01-01 open Assets:Listed");
+    let mut options = ReadOptions::default();
+    options.root = Some("/ledger".into());
+    let error = read_files_from::<f64, _>(&fs, ["/ledger/main.beancount".into()], &options, |_| ())
+        .unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::OutsideRoot);
+    assert_eq!(error.path(), Path::new(expected_path));
+    assert!(!error.to_string().contains("listed"), "{error}");
+}
+
+#[rstest]
+fn should_report_missing_file_of_in_memory_file_system() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", "include \"missing.beancount\"");
+    let result = read_files_from::<f64, _>(
+        &fs,
+        ["/ledger/main.beancount".into()],
+        &ReadOptions::default(),
+        |_| (),
+    );
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains("missing.beancount"), "{message}");
+}
+
+#[rstest]
+fn should_follow_includes_from_virtual_path_of_source() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/accounts.beancount", "2024-01-01 open Assets:Cash");
+    let source = "include \"accounts.beancount\"\n2024-01-02 close Assets:Cash";
+    let mut file = BeancountFile::<f64>::default();
+    read_str_from(
+        &fs,
+        source,
+        "/ledger/main.beancount".as_ref(),
+        &ReadOptions::default(),
+        |entry| file.extend(Some(entry)),
+    )
+    .unwrap();
+    assert_eq!(file.directives.len(), 2);
+    assert_eq!(file.includes, [PathBuf::from("/ledger/accounts.beancount")]);
+}
+
+#[rstest]
+fn should_read_str_with_includes_from_disk() {
+    let source = "include \"./statements/*.beancount\"";
+    let file_path: PathBuf = "tests/samples/uploaded.beancount".into();
+    let mut file = BeancountFile::<f64>::default();
+    read_str_with_options(source, &file_path, &ReadOptions::default(), |entry| {
+        file.extend(Some(entry));
+    })
+    .unwrap();
+    assert_eq!(file.directives.len(), 2);
+}
+
+#[rstest]
+fn should_enforce_limits_on_source() {
+    let mut options = ReadOptions::default();
+    options.limits.max_line_length = Some(10);
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/accounts.beancount", "");
+    let result = read_str_from::<f64, _>(
+        &fs,
+        "2024-01-01 open Assets:Cash",
+        "/ledger/main.beancount".as_ref(),
+        &options,
+        |_| (),
+    );
+    let message = result.unwrap_err().to_string();
+    assert!(
+        message.contains("line 1 is longer than 10 bytes"),
+        "{message}"
+    );
+}
+
+#[rstest]
+fn should_return_include_graph() {
+    let path: PathBuf = "tests/samples/includes.beancount".into();
+    let graph = read_files_with_options::<f64, _>([path], &ReadOptions::default(), |_| ()).unwrap();
+    let includes: Vec<_> = graph
+        .includes()
+        .iter()
+        .map(|include| {
+            (
+                include.from.file_name().unwrap().to_str().unwrap(),
+                include.line_number,
+                include.path.file_name().unwrap().to_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        includes,
+        [
+            ("includes.beancount", 1, "comments.beancount"),
+            ("includes.beancount", 2, "simple.beancount"),
+            ("includes.beancount", 3, "official.beancount"),
+            ("includes.beancount", 4, "includes.beancount"),
+            ("includes.beancount", 5, "cycle.beancount"),
+            ("cycle.beancount", 1, "includes.beancount"),
+        ]
+    );
+}
+
+#[rstest]
+fn should_report_include_cycles_and_duplicates() {
+    let path: PathBuf = "tests/samples/includes.beancount".into();
+    let graph = read_files_with_options::<f64, _>([path], &ReadOptions::default(), |_| ()).unwrap();
+    let diagnostics = graph.diagnostics();
+    let lengths: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| match diagnostic {
+            IncludeDiagnostic::Cycle(includes) => ("cycle", includes.len()),
+            IncludeDiagnostic::Duplicate(includes) => ("duplicate", includes.len()),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(lengths, [("cycle", 1), ("cycle", 2), ("duplicate", 2)]);
+}
+
+#[rstest]
+fn should_record_each_file_matched_by_glob_include() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/main.beancount", "\ninclude \"*/*.beancount\"");
+    fs.insert("/a/1.beancount", "");
+    fs.insert("/b/2.beancount", "");
+    let graph = read_files_from::<f64, _>(
+        &fs,
+        ["/main.beancount".into()],
+        &ReadOptions::default(),
+        |_| (),
+    )
+    .unwrap();
+    let includes: Vec<_> = graph
+        .included_by("/main.beancount".as_ref())
+        .map(|include| (include.line_number, include.path.to_str().unwrap()))
+        .collect();
+    assert_eq!(includes, [(2, "/a/1.beancount"), (2, "/b/2.beancount")]);
+    assert!(graph.diagnostics().is_empty());
+}
+
+#[rstest]
+fn should_expose_kind_and_path_of_error() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", "include \"missing.beancount\"");
+    let error = read_files_from::<f64, _>(
+        &fs,
+        ["/ledger/main.beancount".into()],
+        &ReadOptions::default(),
+        |_| (),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::Io);
+    assert_eq!(error.path(), PathBuf::from("/ledger/missing.beancount"));
+    assert!(error.io_error().is_some());
+    assert!(error.syntax_error().is_none());
+}
+
+#[rstest]
+fn should_expose_include_chain_of_error() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/main.beancount", "\ninclude \"accounts.beancount\"");
+    fs.insert(
+        "/accounts.beancount",
+        "2024-01-01 open Assets:Cash\ninclude \"invalid.beancount\"",
+    );
+    fs.insert("/invalid.beancount", "2024-01-01 open Assets:Cash\n\noops");
+    let error = read_files_from::<f64, _>(
+        &fs,
+        ["/main.beancount".into()],
+        &ReadOptions::default(),
+        |_| (),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::Syntax);
+    assert_eq!(error.syntax_error().unwrap().line_number(), 3);
+    let chain: Vec<_> = error
+        .include_chain()
+        .iter()
+        .map(|include| {
+            (
+                include.from.to_str().unwrap(),
+                include.line_number,
+                include.path.to_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        chain,
+        [
+            ("/main.beancount", 2, "/accounts.beancount"),
+            ("/accounts.beancount", 2, "/invalid.beancount"),
+        ]
+    );
+}
+
+#[rstest]
+fn should_end_include_chain_with_rejected_include() {
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/ledger/main.beancount", "include \"../secret.beancount\"");
+    fs.insert("/secret.beancount", "");
+    let mut options = ReadOptions::default();
+    options.root = Some("/ledger".into());
+    let error = read_files_from::<f64, _>(&fs, ["/ledger/main.beancount".into()], &options, |_| ())
+        .unwrap_err();
+    assert_eq!(error.kind(), ReadFileErrorKind::OutsideRoot);
+    let [include] = error.include_chain() else {
+        panic!("unexpected include chain: {:?}", error.include_chain());
+    };
+    assert_eq!(include.from, PathBuf::from("/ledger/main.beancount"));
+    assert_eq!(include.path, PathBuf::from("/secret.beancount"));
+}
+
+#[rstest]
+fn error_should_be_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<beancount_parser::ReadFileErrorV2>();
+}
+
+#[cfg(feature = "miette")]
+#[rstest]
+fn should_name_source_of_syntax_error_after_file() {
+    use miette::Diagnostic;
+    let mut fs = InMemoryFileSystem::new();
+    fs.insert("/main.beancount", "2024-01-01 oops");
+    let error = read_files_from::<f64, _>(
+        &fs,
+        ["/main.beancount".into()],
+        &ReadOptions::default(),
+        |_| (),
+    )
+    .unwrap_err();
+    let label = error.labels().unwrap().next().unwrap();
+    let contents = error
+        .source_code()
+        .unwrap()
+        .read_span(label.inner(), 0, 0)
+        .unwrap();
+    assert_eq!(contents.name(), Some("/main.beancount"));
+}
+
+#[cfg(feature = "parallel")]
+mod parallel {
+    use super::*;
+    use beancount_parser::{read_files_parallel, read_files_parallel_from};
+
+    fn in_memory_ledger() -> InMemoryFileSystem {
+        let mut fs = InMemoryFileSystem::new();
+        let includes: Vec<_> = (0..20)
+            .map(|i| format!("include \"accounts/{i}.beancount\""))
+            .collect();
+        fs.insert("/ledger/main.beancount", includes.join("\n"));
+        for i in 0..20 {
+            fs.insert(
+                format!("/ledger/accounts/{i}.beancount"),
+                format!(
+                    "2024-01-01 open Assets:A{i}\ninclude \"../shared.beancount\"\n2024-01-02 close Assets:A{i}"
+                ),
+            );
+        }
+        fs.insert("/ledger/shared.beancount", "2024-01-01 open Assets:Shared");
+        fs
+    }
+
+    #[rstest]
+    fn should_emit_entries_in_same_order_as_sequential_reading(
+        #[values(IncludeOrder::Deferred, IncludeOrder::Source)] include_order: IncludeOrder,
+    ) {
+        let fs = in_memory_ledger();
+        let mut options = ReadOptions::default();
+        options.include_order = include_order;
+        let files = || [PathBuf::from("/ledger/main.beancount")];
+        let mut expected = Vec::new();
+        let expected_graph = read_files_from::<f64, _>(&fs, files(), &options, |entry| {
+            expected.push(format!("{entry:?}"));
+        })
+        .unwrap();
+        let mut actual = Vec::new();
+        let graph = read_files_parallel_from::<f64, _>(&fs, files(), &options, |entry| {
+            actual.push(format!("{entry:?}"));
+        })
+        .unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(graph.includes(), expected_graph.includes());
+    }
+
+    #[rstest]
+    fn should_read_files_from_disk_in_parallel() {
+        let path: PathBuf = "tests/samples/includes.beancount".into();
+        let mut file = BeancountFile::<f64>::default();
+        read_files_parallel([path], &ReadOptions::default(), |entry| {
+            file.extend(Some(entry));
+        })
+        .unwrap();
+        assert_eq!(file.directives.len(), 1731);
+        assert_eq!(file.includes.len(), 6);
+    }
+
+    #[rstest]
+    fn should_report_same_error_as_sequential_reading() {
+        let mut fs = in_memory_ledger();
+        fs.insert("/ledger/accounts/3.beancount", "oops");
+        fs.insert("/ledger/accounts/7.beancount", "oops");
+        let files = || [PathBuf::from("/ledger/main.beancount")];
+        let options = ReadOptions::default();
+        let expected = read_files_from::<f64, _>(&fs, files(), &options, |_| ()).unwrap_err();
+        let actual =
+            read_files_parallel_from::<f64, _>(&fs, files(), &options, |_| ()).unwrap_err();
+        assert_eq!(actual.path(), expected.path());
+        assert_eq!(actual.include_chain(), expected.include_chain());
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[rstest]
+    fn should_enforce_limits_in_parallel() {
+        let fs = in_memory_ledger();
+        let mut options = ReadOptions::default();
+        options.limits.max_included_files = Some(5);
+        let error = read_files_parallel_from::<f64, _>(
+            &fs,
+            [PathBuf::from("/ledger/main.beancount")],
+            &options,
+            |_| (),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), ReadFileErrorKind::LimitExceeded);
+    }
+}
@@ -4,7 +4,10 @@ use std::{collections::HashSet, path::Path};
 
 use rstest::rstest;
 
-use beancount_parser::{metadata, parse, Account, BeancountFile, Directive, DirectiveContent};
+use beancount_parser::{
+    metadata, parse, parse_iter, parse_reader, Account, BeancountFile, BookingMethod, Directive,
+    DirectiveContent, IncrementalParse,
+};
 
 const COMMENTS: &str = include_str!("samples/comments.beancount");
 const SIMPLE: &str = include_str!("samples/simple.beancount");
@@ -225,6 +228,39 @@ fn should_parse_close_account(#[case] input: &str, #[case] expected_account: &st
     assert_eq!(close.account.as_str(), expected_account);
 }
 
+#[rstest]
+#[case("STRICT", BookingMethod::Strict)]
+#[case("STRICT_WITH_SIZE", BookingMethod::StrictWithSize)]
+#[case("FIFO", BookingMethod::Fifo)]
+#[case("LIFO", BookingMethod::Lifo)]
+#[case("HIFO", BookingMethod::Hifo)]
+#[case("AVERAGE", BookingMethod::Average)]
+#[case("NONE", BookingMethod::None)]
+#[case("FIFOO", BookingMethod::Other("FIFOO".into()))]
+fn should_parse_typed_booking_method(#[case] method: &str, #[case] expected: BookingMethod) {
+    let input = format!("2014-05-01 open Assets:Checking \"{method}\"");
+    let DirectiveContent::Open(open) = parse_single_directive(&input).content else {
+        panic!("was not an open directive");
+    };
+    assert_eq!(open.booking_method.as_ref(), Some(&expected));
+    assert_eq!(expected.is_known(), method != "FIFOO");
+    assert_eq!(expected.to_string(), method);
+}
+
+#[rstest]
+#[case("", BookingMethod::Strict)]
+#[case("option \"booking_method\" \"FIFO\"", BookingMethod::Fifo)]
+#[case(
+    "option \"booking_method\" \"FIFO\"\noption \"booking_method\" \"HIFO\"",
+    BookingMethod::Hifo
+)]
+#[case("option \"booking_method\" \"FIFOO\"", BookingMethod::Other("FIFOO".into()))]
+#[case("option \"booking_method\" \"fifo\"", BookingMethod::Other("fifo".into()))]
+fn should_parse_booking_method_option(#[case] input: &str, #[case] expected: BookingMethod) {
+    let beancount = parse::<f64>(input).unwrap();
+    assert_eq!(beancount.booking_method(), expected);
+}
+
 #[rstest]
 fn should_parse_option() {
     let beancount = parse::<f64>(r#"option "He\"llo" "world\"!\"""#).unwrap();
@@ -412,6 +448,36 @@ fn should_parse_metadata_currency() {
     assert_eq!(currency.as_str(), "CHF");
 }
 
+fn nested_expression(depth: usize) -> String {
+    format!(
+        "2023-05-27 price CHF {}1{} PLN",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+#[rstest]
+fn should_parse_nested_expression_up_to_default_depth() {
+    let beancount = parse::<f64>(&nested_expression(64)).unwrap();
+    let DirectiveContent::Price(price) = &beancount.directives[0].content else {
+        panic!("was not a price");
+    };
+    assert_eq!(price.amount.value, 1.0);
+}
+
+#[rstest]
+fn should_reject_deeply_nested_expression(#[values(65, 100_000)] depth: usize) {
+    let input = nested_expression(depth);
+    assert_eq!(parse::<f64>(&input).unwrap_err().line_number(), 1);
+    assert!(parse_iter::<f64>(&input).any(|entry| entry.is_err()));
+    assert!(parse_reader::<f64, _>(input.as_bytes()).any(|entry| entry.is_err()));
+    assert!(IncrementalParse::<f64>::new(input.as_str())
+        .error()
+        .is_some());
+    #[cfg(feature = "parallel")]
+    assert!(beancount_parser::parse_parallel::<f64>(&input).is_err());
+}
+
 #[rstest]
 fn should_reject_invalid_input(
     #[values(
@@ -0,0 +1,69 @@
+#![allow(missing_docs)]
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use rstest::rstest;
+
+use beancount_parser::{parse_iter, parse_reader, Entry};
+
+const OFFICIAL: &str = include_str!("samples/official.beancount");
+
+fn from_str(input: &str) -> Result<Vec<Entry<f64>>, u32> {
+    parse_iter::<f64>(input)
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.line_number())
+}
+
+fn from_reader(reader: impl BufRead) -> Result<Vec<Entry<f64>>, u32> {
+    parse_reader::<f64, _>(reader)
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.line_number())
+}
+
+#[rstest]
+fn should_parse_official_example_like_parse_iter(#[values(1, 7, 8192)] capacity: usize) {
+    let reader = BufReader::with_capacity(capacity, OFFICIAL.as_bytes());
+    assert_eq!(from_reader(reader), from_str(OFFICIAL));
+}
+
+#[rstest]
+#[case::empty("")]
+#[case::no_final_line_ending("2024-01-01 open Assets:Cash")]
+#[case::tags("pushtag #a\n2024-01-01 *\npoptag #a\n2024-01-02 *\n")]
+#[case::metadata("2024-01-01 open Assets:Cash\n  a: 1\n\n; comment\n  b: 2\n2024-01-02 *\n")]
+#[case::postings("2024-01-01 *\n  Assets:A 1 CHF\n; comment\n  Assets:B\n2024-01-02 *\n")]
+#[case::multiline_string("2024-01-01 * \"a\n2024-01-02 open B\n\"\n2024-01-03 *\n")]
+#[case::escaped_quote("2024-01-01 * \"a\\\"\n2024-01-02 open B\n\"\n2024-01-03 *\n")]
+#[case::error("2024-01-01 *\n2024-01-02 oops\n2024-01-03 *\n")]
+#[case::error_in_first_line("oops\n2024-01-01 *\n")]
+#[case::error_after_metadata("2024-01-01 *\n  a: 1\n2024-01-02 oops\n")]
+#[case::unterminated_string("2024-01-01 *\n2024-01-02 * \"a\n2024-01-03 *\n")]
+fn should_parse_like_parse_iter(#[case] input: &str) {
+    assert_eq!(from_reader(input.as_bytes()), from_str(input));
+}
+
+struct FailingReader<'a> {
+    content: &'a [u8],
+}
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.content.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::Other, "connection lost"));
+        }
+        self.content.read(buf)
+    }
+}
+
+#[rstest]
+fn should_return_entries_read_before_io_error() {
+    let reader = BufReader::new(FailingReader {
+        content: b"2024-01-01 open Assets:Cash\n2024-01-02 open Assets:Bank\n",
+    });
+    let mut entries = parse_reader::<f64, _>(reader);
+    assert!(entries.next().unwrap().is_ok());
+    let err = entries.next().unwrap().unwrap_err();
+    assert_eq!(err.io_error().unwrap().kind(), io::ErrorKind::Other);
+    assert_eq!(err.line_number(), 3);
+    assert!(entries.next().is_none());
+}
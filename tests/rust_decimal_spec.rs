@@ -3,7 +3,9 @@
 use rstest::rstest;
 use rust_decimal::Decimal;
 
-use beancount_parser::{parse, Directive, DirectiveContent, Posting, Transaction};
+use beancount_parser::{
+    parse, Directive, DirectiveContent, Number, Posting, Transaction, WeightError,
+};
 
 #[rstest]
 #[case("10 CHF", 10, "CHF")]
@@ -23,6 +25,35 @@ fn should_parse_amount(
     assert_eq!(amount.currency.as_str(), expected_currency);
 }
 
+#[rstest]
+#[case::division_by_zero("10 / 0 USD")]
+#[case::division_by_zero_expression("10 / (2 - 2) USD")]
+#[case::multiplication_overflow("79228162514264337593543950335 * 2 USD")]
+#[case::addition_overflow("79228162514264337593543950335 + 1 USD")]
+#[case::subtraction_overflow("-79228162514264337593543950335 - 1 USD")]
+fn should_reject_invalid_arithmetic(#[case] amount: &str) {
+    let input = format!("2023-05-17 *\n  Assets:Cash 1 USD\n  Assets:Bank {amount}");
+    let error = parse::<Decimal>(&input).unwrap_err();
+    assert_eq!(error.line_number(), 3);
+    assert!(parse::<Number<Decimal>>(&input).is_err());
+}
+
+#[rstest]
+fn should_reject_invalid_arithmetic_in_metadata() {
+    let input = "2023-05-17 open Assets:Cash\n  ratio: 1 / 0";
+    let error = parse::<Decimal>(input).unwrap_err();
+    assert_eq!(error.line_number(), 2);
+}
+
+#[rstest]
+#[case::unit_price("79228162514264337593543950335 USD @ 2 EUR")]
+#[case::cost("79228162514264337593543950335 ACME {2 USD}")]
+fn should_not_compute_overflowing_weight(#[case] amount: &str) {
+    let input = format!("2023-05-17 *\n  Assets:Cash {amount}");
+    let posting = parse_single_posting(&input);
+    assert_eq!(posting.weight(), Err(WeightError::Overflow));
+}
+
 fn parse_single_directive(input: &str) -> Directive<Decimal> {
     let directives = parse(input).expect("parsing should succeed").directives;
     assert_eq!(
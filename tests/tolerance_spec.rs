@@ -0,0 +1,112 @@
+#![allow(clippy::pedantic, missing_docs)]
+
+use rstest::rstest;
+use rust_decimal::Decimal;
+
+use beancount_parser::{
+    parse, DirectiveContent, InterpolationError, Number, Tolerances, TransactionBalanceError,
+};
+
+fn check(input: &str) -> Result<(), TransactionBalanceError<Decimal>> {
+    let mut beancount = parse::<Number<Decimal>>(input).unwrap();
+    let tolerances = Tolerances::from_options(&beancount.options);
+    let DirectiveContent::Transaction(trx) = beancount.directives.remove(0).content else {
+        panic!("was not a transaction");
+    };
+    trx.check_balance(&tolerances)
+}
+
+fn residuals(input: &str) -> Vec<String> {
+    match check(input) {
+        Ok(()) => Vec::new(),
+        Err(TransactionBalanceError::Unbalanced(residuals)) => residuals
+            .into_iter()
+            .map(|residual| {
+                format!(
+                    "{} {} ~ {}",
+                    residual.amount.value, residual.amount.currency, residual.tolerance
+                )
+            })
+            .collect(),
+        Err(err) => panic!("unexpected error: {err:?}"),
+    }
+}
+
+#[rstest]
+#[case::balanced("2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food 10 CHF")]
+#[case::within_inferred_tolerance(
+    "2024-01-01 *\n  Assets:Cash -10.004 CHF\n  Expenses:Food 10.00 CHF"
+)]
+#[case::negative_residual_within_tolerance(
+    "2024-01-01 *\n  Assets:Cash -10.00 CHF\n  Expenses:Food 10.005 CHF"
+)]
+#[case::posting_without_amount("2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food")]
+#[case::unit_price("2024-01-01 *\n  Assets:Cash 10.00 EUR @ 1.1234 CHF\n  Assets:Bank -11.23 CHF")]
+#[case::total_price("2024-01-01 *\n  Assets:Cash -10 EUR @@ 11 CHF\n  Assets:Bank 11 CHF")]
+#[case::cost("2024-01-01 *\n  Assets:Stock 2 ACME {100 CHF}\n  Assets:Bank -200 CHF")]
+#[case::default_tolerance(
+    "option \"inferred_tolerance_default\" \"CHF:0.1\"\n2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food 10.1 CHF"
+)]
+#[case::default_tolerance_of_all_currencies(
+    "option \"inferred_tolerance_default\" \"*:0.1\"\n2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food 10.1 CHF"
+)]
+#[case::multiplier(
+    "option \"inferred_tolerance_multiplier\" \"1.2\"\n2024-01-01 *\n  Assets:Cash -10.00 CHF\n  Expenses:Food 10.011 CHF"
+)]
+#[case::cost_tolerance(
+    "option \"infer_tolerance_from_cost\" \"TRUE\"\n2024-01-01 *\n  Assets:Stock 10.0 ACME {100 CHF}\n  Assets:Bank -1000.3 CHF"
+)]
+#[case::price_tolerance(
+    "option \"infer_tolerance_from_cost\" \"TRUE\"\n2024-01-01 *\n  Assets:Cash 10.0 EUR @ 2 CHF\n  Assets:Bank -20.05 CHF"
+)]
+fn should_accept_balanced_transaction(#[case] input: &str) {
+    assert_eq!(residuals(input), Vec::<String>::new());
+}
+
+#[rstest]
+#[case::integers(
+    "2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food 9 CHF",
+    &["-1 CHF ~ 0"]
+)]
+#[case::beyond_inferred_tolerance(
+    "2024-01-01 *\n  Assets:Cash -10.00 CHF\n  Expenses:Food 10.011 CHF",
+    &["0.011 CHF ~ 0.005"]
+)]
+#[case::least_precise_number(
+    "2024-01-01 *\n  Assets:Cash -10.0 CHF\n  Expenses:Food 9.94 CHF\n  Expenses:Other 0.001 CHF",
+    &["-0.059 CHF ~ 0.05"]
+)]
+#[case::multiple_currencies(
+    "2024-01-01 *\n  Assets:Cash -10 CHF\n  Assets:Cash -10.00 EUR\n  Expenses:Food 9 CHF\n  Expenses:Food 10.00 EUR\n  Expenses:Fuel 1.00 USD",
+    &["-1 CHF ~ 0", "1.00 USD ~ 0.005"]
+)]
+#[case::price_without_cost_tolerance(
+    "2024-01-01 *\n  Assets:Cash 10.0 EUR @ 2 CHF\n  Assets:Bank -20.05 CHF",
+    &["-0.05 CHF ~ 0.005"]
+)]
+#[case::cost_without_cost_tolerance(
+    "2024-01-01 *\n  Assets:Stock 10.0 ACME {100 CHF}\n  Assets:Bank -1000.3 CHF",
+    &["-0.3 CHF ~ 0.05"]
+)]
+fn should_report_residuals_exceeding_tolerance(#[case] input: &str, #[case] expected: &[&str]) {
+    assert_eq!(residuals(input), expected);
+}
+
+#[rstest]
+#[case::ambiguous(
+    "2024-01-01 *\n  Assets:Cash -10 CHF\n  Expenses:Food\n  Expenses:Fuel",
+    InterpolationError::AmbiguousPostings(vec![1, 2])
+)]
+#[case::incomplete_cost(
+    "2024-01-01 *\n  Assets:Stock -2 ACME {}\n  Assets:Bank 200 CHF",
+    InterpolationError::IncompletePosting(0)
+)]
+fn should_report_transaction_that_cannot_be_interpolated(
+    #[case] input: &str,
+    #[case] expected: InterpolationError,
+) {
+    assert_eq!(
+        check(input),
+        Err(TransactionBalanceError::Interpolation(expected))
+    );
+}
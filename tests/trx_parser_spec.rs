@@ -6,7 +6,7 @@ use rstest::rstest;
 
 use beancount_parser::{
     metadata, parse, parse_iter, Directive, DirectiveContent, Entry, Posting, PostingPrice,
-    Transaction,
+    Transaction, WeightError,
 };
 
 const COMMENTS: &str = include_str!("samples/comments.beancount");
@@ -382,6 +382,18 @@ poptag #foo
     );
 }
 
+#[rstest]
+#[case("Assets:Cash 1 CHF {1 EUR}", None)]
+#[case("Assets:Cash 1 CHF {\"lot\"}", Some("lot"))]
+#[case("Assets:Cash 1 CHF {1 EUR, \"lot\"}", Some("lot"))]
+#[case("Assets:Cash 1 CHF {\"lot\", 2023-05-19, 1 EUR}", Some("lot"))]
+#[case("Assets:Cash 1 CHF {2023-05-19 , 1 EUR,\"lot\"}", Some("lot"))]
+fn should_parse_cost_label(#[case] input: &str, #[case] expected: Option<&str>) {
+    let input = format!("2023-05-17 *\n  {input}",);
+    let cost = parse_single_posting(&input).cost.unwrap();
+    assert_eq!(cost.label.as_deref(), expected);
+}
+
 #[rstest]
 fn should_reject_invalid_input(
     #[values(
@@ -411,6 +423,9 @@ fn should_reject_invalid_input(
         "2023-05-19 *\n  Assets:Cash 1 CHF {1 EUR,}",
         "2023-05-19 *\n  Assets:Cash 1 CHF {, 2023-05-19}",
         "2023-05-19 *\n  Assets:Cash 1 CHF {,}",
+        "2023-05-19 *\n  Assets:Cash 1 CHF {1 EUR, 2 EUR}",
+        "2023-05-19 *\n  Assets:Cash 1 CHF {2023-05-19, 2023-05-20}",
+        "2023-05-19 *\n  Assets:Cash 1 CHF {\"a\", \"b\"}",
         "pushtag#test",
         "pushtag test",
         "pushtag",
@@ -427,6 +442,38 @@ fn should_reject_invalid_input(
     assert!(result.is_err(), "{result:#?}");
 }
 
+#[rstest]
+#[case::amount("2023-05-15 *\n  Assets:Cash -10 CHF", -10.0, "CHF")]
+#[case::unit_price("2023-05-15 *\n  Assets:Cash 10 EUR @ 1.5 CHF", 15.0, "CHF")]
+#[case::total_price("2023-05-15 *\n  Assets:Cash 10 EUR @@ 15 CHF", 15.0, "CHF")]
+#[case::negative_total_price("2023-05-15 *\n  Assets:Cash -10 EUR @@ 15 CHF", -15.0, "CHF")]
+#[case::cost("2023-05-15 *\n  Assets:Stock 2 ACME {100 CHF}", 200.0, "CHF")]
+#[case::cost_with_date("2023-05-15 *\n  Assets:Stock -2 ACME {2023-01-01, 100 CHF}", -200.0, "CHF")]
+#[case::cost_over_price("2023-05-15 *\n  Assets:Stock -2 ACME {100 CHF} @ 120 EUR", -200.0, "CHF")]
+fn should_compute_posting_weight(
+    #[case] input: &str,
+    #[case] expected_value: f64,
+    #[case] expected_currency: &str,
+) {
+    let weight = parse_single_posting(input).weight().unwrap();
+    assert_eq!(weight.value, expected_value);
+    assert_eq!(weight.currency.as_str(), expected_currency);
+}
+
+#[rstest]
+#[case::no_amount("2023-05-15 *\n  Assets:Cash", WeightError::MissingAmount)]
+#[case::no_cost_amount("2023-05-15 *\n  Assets:Stock -2 ACME {}", WeightError::MissingCost)]
+#[case::only_cost_date(
+    "2023-05-15 *\n  Assets:Stock -2 ACME {2023-01-01}",
+    WeightError::MissingCost
+)]
+fn should_not_compute_weight_of_incomplete_posting(
+    #[case] input: &str,
+    #[case] expected: WeightError,
+) {
+    assert_eq!(parse_single_posting(input).weight(), Err(expected));
+}
+
 fn parse_single_directive(input: &str) -> Directive<f64> {
     let directives = parse(input).expect("parsing should succeed").directives;
     assert_eq!(
@@ -6,6 +6,7 @@ use rstest::rstest;
 
 use beancount_parser::{
     parse, parse_iter, BeancountFile, Currency, Date, Directive, DirectiveContent, Entry, Error,
+    Number,
 };
 
 fn is_normal<T: Sized + Send + Sync + Unpin>() {}
@@ -26,6 +27,33 @@ fn result_entry_type_should_be_normal() {
     is_clone::<Result<Entry<f32>, Error>>();
 }
 
+#[test]
+fn number_type_should_be_normal() {
+    is_normal::<BeancountFile<Number<f64>>>();
+    is_debug::<BeancountFile<Number<f64>>>();
+    is_clone::<BeancountFile<Number<f64>>>();
+}
+
+#[rstest]
+#[case("2023-05-17 *\n  Assets:Cash 10 CHF", "10", Some(0))]
+#[case("2023-05-17 *\n  Assets:Cash 10.00 CHF", "10.00", Some(2))]
+#[case("2023-05-17 *\n  Assets:Cash 1,000.50 CHF", "1000.50", Some(2))]
+#[case("2023-05-17 *\n  Assets:Cash -0.125 CHF", "-0.125", Some(3))]
+#[case("2023-05-17 *\n  Assets:Cash 2 * 1.25 CHF", "2.5", Some(2))]
+fn number_should_preserve_written_precision(
+    #[case] input: &str,
+    #[case] expected_display: &str,
+    #[case] expected_precision: Option<u32>,
+) {
+    let file: BeancountFile<Number<f64>> = input.parse().unwrap();
+    let DirectiveContent::Transaction(ref trx) = file.directives[0].content else {
+        unreachable!("was not a transaction")
+    };
+    let value = &trx.postings[0].amount.as_ref().unwrap().value;
+    assert_eq!(value.to_string(), expected_display);
+    assert_eq!(value.precision(), expected_precision);
+}
+
 #[test]
 fn error_debug_impl_is_succinct() {
     let input = "2023-06-11 * Oops\n\n\n\n\n; end comment";
@@ -0,0 +1,353 @@
+#![allow(clippy::pedantic, missing_docs)]
+
+use rstest::rstest;
+use rust_decimal::Decimal;
+
+use beancount_parser::{
+    check_accounts, check_balances, expand_pads, parse, AccountErrorKind, BeancountFile, Date,
+    DirectiveContent, Number,
+};
+
+const LEDGER: &str = r#"
+2024-01-01 open Assets:Bank:Checking
+2024-01-01 open Assets:Bank:Savings
+2024-01-01 open Assets:Broker "FIFO"
+2024-01-01 open Equity:Opening
+2024-01-02 * "Opening"
+  Assets:Bank:Checking   100.00 CHF
+  Assets:Bank:Savings    200.00 CHF
+  Equity:Opening
+2024-01-03 * "Buy"
+  Assets:Broker   10 ACME {10 CHF}
+  Assets:Bank:Checking
+2024-01-04 * "Sell"
+  Assets:Broker   -4 ACME {}
+  Assets:Bank:Checking
+"#;
+
+fn parse_ledger(balances: &str) -> BeancountFile<Number<Decimal>> {
+    parse(&format!("{LEDGER}{balances}")).unwrap()
+}
+
+/// Returns the line numbers and messages of the failed balance assertions
+fn failures(balances: &str) -> Vec<(u32, String)> {
+    let beancount = parse_ledger(balances);
+    check_balances(&beancount.directives, &beancount.options)
+        .into_iter()
+        .map(|error| (error.directive.line_number, error.to_string()))
+        .collect()
+}
+
+#[rstest]
+#[case::before_transactions("2024-01-02 balance Assets:Bank:Checking 0 CHF")]
+#[case::start_of_date("2024-01-03 balance Assets:Bank:Checking 100.00 CHF")]
+#[case::after_transactions("2024-01-05 balance Assets:Bank:Checking 40.00 CHF")]
+#[case::interpolated("2024-01-05 balance Equity:Opening -300.00 CHF")]
+#[case::sub_accounts("2024-01-05 balance Assets:Bank 240.00 CHF")]
+#[case::booked_units("2024-01-05 balance Assets:Broker 6 ACME")]
+#[case::other_currency("2024-01-05 balance Assets:Bank 0 EUR")]
+#[case::unknown_account("2024-01-05 balance Assets:Unknown 0 CHF")]
+#[case::inferred_tolerance("2024-01-05 balance Assets:Bank:Checking 40.01 CHF")]
+#[case::explicit_tolerance("2024-01-05 balance Assets:Bank:Checking 40.5 ~ 0.5 CHF")]
+#[case::in_any_order(
+    "2024-01-06 balance Assets:Bank:Checking 40.00 CHF\n2024-01-02 balance Assets:Bank:Checking 0 CHF"
+)]
+fn should_accept_valid_balance(#[case] balances: &str) {
+    assert_eq!(failures(balances), []);
+}
+
+#[rstest]
+#[case::same_date(
+    "2024-01-02 balance Assets:Bank:Checking 100.00 CHF",
+    "Balance failed for Assets:Bank:Checking: expected 100.00 CHF, but was 0 CHF (difference: -100.00 CHF)"
+)]
+#[case::sub_accounts(
+    "2024-01-05 balance Assets:Bank 250.00 CHF",
+    "Balance failed for Assets:Bank: expected 250.00 CHF, but was 240.00 CHF (difference: -10.00 CHF)"
+)]
+#[case::integer(
+    "2024-01-05 balance Assets:Broker 5 ACME",
+    "Balance failed for Assets:Broker: expected 5 ACME, but was 6 ACME (difference: 1 ACME)"
+)]
+#[case::inferred_tolerance(
+    "2024-01-05 balance Assets:Bank:Checking 40.02 CHF",
+    "Balance failed for Assets:Bank:Checking: expected 40.02 CHF, but was 40 CHF (difference: -0.02 CHF)"
+)]
+#[case::explicit_tolerance(
+    "2024-01-05 balance Assets:Bank:Checking 41 ~ 0.5 CHF",
+    "Balance failed for Assets:Bank:Checking: expected 41 CHF, but was 40 CHF (difference: -1 CHF)"
+)]
+fn should_report_failed_balance(#[case] balance: &str, #[case] expected: &str) {
+    assert_eq!(failures(balance), [(16, expected.to_owned())]);
+}
+
+#[test]
+fn should_report_expected_and_actual_amounts() {
+    let beancount = parse_ledger("2024-01-05 balance Assets:Bank 250.00 ~ 0.01 CHF");
+    let errors = check_balances(&beancount.directives, &beancount.options);
+    assert_eq!(errors.len(), 1);
+    let error = &errors[0];
+    assert_eq!(error.expected.value, Decimal::new(25000, 2));
+    assert_eq!(error.expected.currency.as_str(), "CHF");
+    let actual = error.actual.as_ref().unwrap();
+    assert_eq!(actual.value, Decimal::from(240));
+    assert_eq!(actual.currency.as_str(), "CHF");
+    assert_eq!(error.difference, Some(Decimal::from(-10)));
+    assert_eq!(error.tolerance, Decimal::new(1, 2));
+}
+
+#[test]
+fn should_report_overflowing_units() {
+    let beancount: BeancountFile<Number<Decimal>> = parse(
+        r#"
+2024-01-01 open Assets:Bank:Checking
+2024-01-01 open Assets:Bank:Savings
+2024-01-01 open Equity:Checking
+2024-01-01 open Equity:Savings
+2024-01-02 *
+  Assets:Bank:Checking   79228162514264337593543950335 CHF
+  Equity:Checking
+2024-01-02 *
+  Assets:Bank:Savings    79228162514264337593543950335 CHF
+  Equity:Savings
+2024-01-03 balance Assets:Bank 0 CHF
+"#,
+    )
+    .unwrap();
+    let errors = check_balances(&beancount.directives, &beancount.options);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].actual, None);
+    assert_eq!(errors[0].difference, None);
+    assert_eq!(
+        errors[0].to_string(),
+        "Balance failed for Assets:Bank: expected 0 CHF, but the units of the account overflow"
+    );
+}
+
+#[rstest]
+#[case("2024-01-05 balance Assets:Bank:Checking 40.03 CHF", 1)]
+#[case("2024-01-05 balance Assets:Bank:Checking 40.02 CHF", 0)]
+fn should_use_tolerance_multiplier(#[case] balance: &str, #[case] expected: usize) {
+    let beancount = parse_ledger(&format!(
+        "option \"inferred_tolerance_multiplier\" \"1\"\n{balance}"
+    ));
+    assert_eq!(
+        check_balances(&beancount.directives, &beancount.options).len(),
+        expected
+    );
+}
+
+const PADS: &str = r#"
+2024-01-01 open Assets:Cash
+2024-01-01 open Expenses:Food
+2024-01-01 open Equity:Opening
+2024-01-01 pad Assets:Cash Equity:Opening
+2024-01-02 * "Shop"
+  Assets:Cash    -10.00 CHF
+  Expenses:Food
+2024-01-03 balance Assets:Cash 90.00 CHF
+2024-01-03 balance Assets:Cash 20 EUR
+"#;
+
+/// Returns the padding transactions, with their date and line number
+fn paddings(input: &str) -> Vec<String> {
+    let beancount = parse::<Number<Decimal>>(input).unwrap();
+    let (directives, _) = expand_pads(&beancount.directives, &beancount.options);
+    directives
+        .iter()
+        .filter_map(|directive| {
+            let DirectiveContent::Transaction(trx) = &directive.content else {
+                return None;
+            };
+            (trx.flag == Some('P')).then(|| {
+                let postings: Vec<String> = trx
+                    .postings
+                    .iter()
+                    .map(|posting| {
+                        let amount = posting.amount.as_ref().unwrap();
+                        format!("{} {} {}", posting.account, amount.value, amount.currency)
+                    })
+                    .collect();
+                format!(
+                    "{}-{:02}-{:02} line {}: {}",
+                    directive.date.year,
+                    directive.date.month,
+                    directive.date.day,
+                    directive.line_number,
+                    postings.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn unused_pads(input: &str) -> Vec<u32> {
+    let beancount = parse::<Number<Decimal>>(input).unwrap();
+    let (_, errors) = expand_pads(&beancount.directives, &beancount.options);
+    errors
+        .into_iter()
+        .map(|error| error.directive.line_number)
+        .collect()
+}
+
+#[test]
+fn should_pad_each_currency() {
+    assert_eq!(
+        paddings(PADS),
+        [
+            "2024-01-01 line 5: Assets:Cash 100.00 CHF, Equity:Opening -100.00 CHF",
+            "2024-01-01 line 5: Assets:Cash 20 EUR, Equity:Opening -20 EUR",
+        ]
+    );
+    assert_eq!(unused_pads(PADS), []);
+}
+
+#[test]
+fn should_insert_padding_after_pad_directive() {
+    let beancount = parse::<Number<Decimal>>(PADS).unwrap();
+    let (directives, _) = expand_pads(&beancount.directives, &beancount.options);
+    assert_eq!(directives.len(), beancount.directives.len() + 2);
+    let DirectiveContent::Transaction(trx) = &directives[4].content else {
+        panic!("was not a transaction");
+    };
+    assert_eq!(
+        trx.narration.as_deref(),
+        Some("(Padding inserted for Balance of 90.00 CHF for difference 100.00 CHF)")
+    );
+    assert_eq!(check_balances(&directives, &beancount.options).len(), 0);
+}
+
+#[test]
+fn should_pad_only_first_balance_of_currency() {
+    let input = format!("{PADS}2024-01-04 balance Assets:Cash 100.00 CHF\n");
+    assert_eq!(paddings(&input).len(), 2);
+    let beancount = parse::<Number<Decimal>>(&input).unwrap();
+    let (directives, _) = expand_pads(&beancount.directives, &beancount.options);
+    let errors = check_balances(&directives, &beancount.options);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].directive.line_number, 11);
+}
+
+#[rstest]
+#[case::no_balance("2024-01-01 open Assets:Cash\n2024-01-01 pad Assets:Cash Equity:Opening", &[2])]
+#[case::no_difference(
+    "2024-01-01 open Assets:Cash\n2024-01-01 pad Assets:Cash Equity:Opening\n2024-01-02 balance Assets:Cash 0 CHF",
+    &[2]
+)]
+#[case::other_account(
+    "2024-01-01 pad Assets:Cash Equity:Opening\n2024-01-02 balance Assets:Bank 10 CHF",
+    &[1]
+)]
+#[case::replaced(
+    "2024-01-01 pad Assets:Cash Equity:Opening\n2024-01-02 pad Assets:Cash Equity:Opening\n2024-01-03 balance Assets:Cash 10 CHF",
+    &[1]
+)]
+#[case::after_balance(
+    "2024-01-02 balance Assets:Cash 10 CHF\n2024-01-02 pad Assets:Cash Equity:Opening",
+    &[2]
+)]
+fn should_report_unused_pads(#[case] input: &str, #[case] expected: &[u32]) {
+    assert_eq!(unused_pads(input), expected);
+}
+
+/// Returns the line numbers and messages of the account errors
+fn account_errors(input: &str) -> Vec<(u32, String)> {
+    let beancount = parse::<Number<Decimal>>(input).unwrap();
+    check_accounts(&beancount.directives, &beancount.options)
+        .into_iter()
+        .map(|error| (error.directive.line_number, error.to_string()))
+        .collect()
+}
+
+#[rstest]
+#[case::ledger(LEDGER)]
+#[case::balances(&format!("{LEDGER}2024-01-05 balance Assets:Bank:Checking 40.00 CHF"))]
+#[case::pads(PADS)]
+#[case::open_date("2024-01-01 open Assets:Cash\n2024-01-01 balance Assets:Cash 0 CHF")]
+#[case::close_date(
+    "2024-01-01 open Assets:Cash\n2024-01-02 close Assets:Cash\n2024-01-02 balance Assets:Cash 0 CHF"
+)]
+#[case::emptied(
+    "2024-01-01 open Assets:Cash\n2024-01-01 open Equity:Opening\n2024-01-02 *\n  Assets:Cash 1 CHF\n  Equity:Opening\n2024-01-03 *\n  Assets:Cash -1 CHF\n  Equity:Opening\n2024-01-03 close Assets:Cash"
+)]
+#[case::sub_account_not_empty(
+    "2024-01-01 open Assets:Cash\n2024-01-01 open Assets:Cash:Wallet\n2024-01-01 open Equity:Opening\n2024-01-02 *\n  Assets:Cash:Wallet 1 CHF\n  Equity:Opening\n2024-01-03 close Assets:Cash"
+)]
+fn should_accept_open_accounts(#[case] input: &str) {
+    assert_eq!(account_errors(input), []);
+}
+
+#[rstest]
+#[case::posting_not_open(
+    "2024-01-01 open Assets:Cash\n2024-01-02 *\n  Assets:Cash -1 CHF\n  Expenses:Food",
+    &[(2, "Account Expenses:Food is not open")]
+)]
+#[case::posting_before_open(
+    "2024-01-02 open Assets:Cash\n2024-01-02 open Equity:Opening\n2024-01-01 *\n  Assets:Cash 1 CHF\n  Equity:Opening",
+    &[(3, "Account Assets:Cash is not open"), (3, "Account Equity:Opening is not open")]
+)]
+#[case::posting_after_close(
+    "2024-01-01 open Assets:Cash\n2024-01-01 open Equity:Opening\n2024-01-02 close Assets:Cash\n2024-01-03 *\n  Assets:Cash 1 CHF\n  Equity:Opening",
+    &[(4, "Account Assets:Cash is closed since 2024-01-02")]
+)]
+#[case::balance(
+    "2024-01-01 balance Assets:Cash 0 CHF",
+    &[(1, "Account Assets:Cash is not open")]
+)]
+#[case::pad(
+    "2024-01-01 open Assets:Cash\n2024-01-01 pad Assets:Cash Equity:Opening",
+    &[(2, "Account Equity:Opening is not open")]
+)]
+#[case::close_not_open("2024-01-01 close Assets:Cash", &[(1, "Account Assets:Cash is not open")])]
+#[case::close_twice(
+    "2024-01-01 open Assets:Cash\n2024-01-02 close Assets:Cash\n2024-01-03 close Assets:Cash",
+    &[(3, "Account Assets:Cash is closed since 2024-01-02")]
+)]
+#[case::duplicate_open(
+    "2024-01-01 open Assets:Cash\n2024-01-02 open Assets:Cash",
+    &[(2, "Account Assets:Cash is already open since 2024-01-01")]
+)]
+#[case::reopen(
+    "2024-01-01 open Assets:Cash\n2024-01-02 close Assets:Cash\n2024-01-03 open Assets:Cash\n2024-01-04 balance Assets:Cash 0 CHF",
+    &[
+        (3, "Account Assets:Cash is already open since 2024-01-01"),
+        (4, "Account Assets:Cash is closed since 2024-01-02"),
+    ]
+)]
+#[case::close_not_empty(
+    "2024-01-01 open Assets:Cash\n2024-01-01 open Equity:Opening\n2024-01-02 *\n  Assets:Cash 1 CHF\n  Equity:Opening\n2024-01-02 close Assets:Cash",
+    &[(6, "Account Assets:Cash is not empty")]
+)]
+fn should_report_account_errors(#[case] input: &str, #[case] expected: &[(u32, &str)]) {
+    let expected: Vec<(u32, String)> = expected
+        .iter()
+        .map(|(line, message)| (*line, (*message).to_owned()))
+        .collect();
+    assert_eq!(account_errors(input), expected);
+}
+
+#[test]
+fn should_report_account_error_kind() {
+    let beancount = parse::<Number<Decimal>>(
+        "2024-01-01 open Assets:Cash\n2024-01-02 close Assets:Cash\n2024-01-03 balance Assets:Cash 0 CHF",
+    )
+    .unwrap();
+    let errors = check_accounts(&beancount.directives, &beancount.options);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].account.as_str(), "Assets:Cash");
+    assert_eq!(
+        errors[0].kind,
+        AccountErrorKind::Closed(Date::new(2024, 1, 2))
+    );
+}
+
+#[test]
+fn should_report_padded_account_not_empty() {
+    let input = format!("{PADS}2024-01-04 close Assets:Cash\n");
+    let beancount = parse::<Number<Decimal>>(&input).unwrap();
+    let (directives, _) = expand_pads(&beancount.directives, &beancount.options);
+    let errors = check_accounts(&directives, &beancount.options);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, AccountErrorKind::NotEmpty);
+    assert_eq!(errors[0].directive.line_number, 11);
+}